@@ -0,0 +1,109 @@
+//! Times the whole pipeline on a generated 30-minute chart.
+//!
+//! Run with `cargo bench`. `cargo test` runs each step once, so the bench
+//! keeps working as the library changes.
+
+use std::time::{Duration, Instant};
+
+use drs_converter::{lint, parse_ssf, stats, Timeline};
+
+const BPM: usize = 180;
+const MINUTES: usize = 30;
+const RUNS: usize = 10;
+
+/// A 4/4 chart at `BPM` long enough to last `MINUTES`: a sixteenth-note
+/// stream for the right foot, and a left-foot slide from each measure into
+/// the next with left steps in the gaps.
+fn marathon_chart() -> String {
+    let measures = MINUTES * BPM / 4;
+    let hold_id = |measure: usize| {
+        char::from_digit((measure % 36) as u32, 36)
+            .unwrap()
+            .to_ascii_uppercase()
+    };
+    let mut ssf = format!("#TITLE Marathon\n#BPM01: {}\n#00008: 1\n#START\n", BPM);
+    for measure in 0..measures {
+        ssf += &format!("{}\n", measure);
+        for step in 0..16 {
+            let mut notes = Vec::new();
+            if step % 2 == 1 {
+                notes.push(format!("1{:X}2", 8 + step % 6));
+            }
+            if measure > 0 && step == 0 {
+                notes.push(format!("6{}A3", hold_id(measure - 1)));
+            }
+            if measure > 0 && step == 4 {
+                notes.push(format!("7{}83", hold_id(measure - 1)));
+            }
+            if (4..=8).contains(&step) && step % 2 == 0 {
+                notes.push(format!("0{:X}1", step - 4));
+            }
+            if measure + 1 < measures && step == 8 {
+                notes.push(format!("4{}83", hold_id(measure)));
+            }
+            if !notes.is_empty() {
+                ssf += &format!("{}:{}\n", step * 12, notes.join(","));
+            }
+        }
+    }
+    ssf += "END\n";
+    ssf
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    // `cargo bench` passes `--bench`; `cargo test` doesn't.
+    let runs = if std::env::args().any(|arg| arg == "--bench") {
+        RUNS
+    } else {
+        1
+    };
+    let mut times: Vec<Duration> = (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    println!(
+        "{:<12} median {:>10.2?}  fastest {:>10.2?}",
+        name,
+        times[runs / 2],
+        times[0]
+    );
+}
+
+fn main() {
+    let source = marathon_chart();
+    let chart = parse_ssf(&source).unwrap();
+    println!(
+        "{} minutes at {} BPM: {} measures, {} lines",
+        MINUTES,
+        BPM,
+        chart.measures.len(),
+        source.lines().count()
+    );
+
+    bench("parse", || {
+        parse_ssf(&source).unwrap();
+    });
+    bench("holds", || {
+        chart.holds().unwrap();
+    });
+    bench("to_drs_xml", || {
+        chart.to_drs_xml().unwrap();
+    });
+    let timeline = Timeline::new(&chart);
+    bench("timeline", || {
+        for note in &chart.events {
+            std::hint::black_box(timeline.ms_at(note.measure, note.tick));
+            std::hint::black_box(timeline.dt_at(note.measure, note.tick));
+        }
+    });
+    bench("lint", || {
+        assert_eq!(lint(&chart), []);
+    });
+    bench("stats", || {
+        stats(&chart).unwrap();
+    });
+}
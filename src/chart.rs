@@ -0,0 +1,242 @@
+use std::fmt;
+
+use crate::note::NoteEvent;
+use crate::rational::{gcd, Rational};
+use crate::tempo::TempoMap;
+
+/// Ticks a measure is split into unless the chart gives another resolution.
+pub const TICKS_PER_MEASURE: usize = 192;
+
+/// Measures a chart can have, so a stray measure number is an error rather
+/// than billions of empty measures. That is over a day of 4/4 at 240 BPM.
+pub(crate) const MAX_MEASURES: usize = 100_000;
+
+/// Measures the SSF header's `#mmmxx:` channels can name, in three digits.
+/// Only tempo and time signature changes have to fit in these.
+pub(crate) const HEADER_MEASURES: usize = 1000;
+
+/// Lanes across the pad, each 4096 position units wide in DRS XML.
+pub const LANES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        })
+    }
+}
+
+/// The `#COMMAND argument` lines at the top of an SSF file.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Header {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub designer: Option<String>,
+    pub difficulty: Option<Difficulty>,
+    pub play_level: Option<String>,
+    pub song_id: Option<String>,
+    pub wave: Option<String>,
+    /// `WAVEOFFSET` in milliseconds; the header gives it in seconds.
+    pub wave_offset: Option<i32>,
+    pub jacket: Option<String>,
+    pub padding_bars: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeSignature {
+    pub num: u32,
+    pub denomi: u32,
+}
+
+impl TimeSignature {
+    pub const COMMON: TimeSignature = TimeSignature { num: 4, denomi: 4 };
+
+    /// Parses either a signature such as `7/8` or a measure length in
+    /// quarter-note beats such as `3.5`, the form `#mmm02:` uses.
+    pub fn parse(value: &str) -> Option<TimeSignature> {
+        if let Some((num, denomi)) = value.split_once('/') {
+            let (num, denomi) = (num.parse::<u32>().ok()?, denomi.parse::<u32>().ok()?);
+            return (num > 0 && denomi > 0).then_some(TimeSignature { num, denomi });
+        }
+        let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+        if fraction.len() > 3 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let scale = 10_u32.pow(fraction.len() as u32);
+        let fraction = fraction.parse::<u32>().unwrap_or(0);
+        let beats = whole.parse::<u32>().ok()?.checked_mul(scale)? + fraction;
+        if beats == 0 {
+            return None;
+        }
+        // `beats` quarter notes make `beats / 4` of a whole note.
+        let divisor = gcd(beats as i128, (scale * 4) as i128) as u32;
+        let (mut num, mut denomi) = (beats / divisor, scale * 4 / divisor);
+        while denomi < 4 {
+            num *= 2;
+            denomi *= 2;
+        }
+        Some(TimeSignature { num, denomi })
+    }
+
+    /// Length of a measure in this signature, in quarter-note beats.
+    pub fn beats(&self) -> Rational {
+        Rational::new(self.num as i128 * 4, self.denomi as i128)
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        TimeSignature::COMMON
+    }
+}
+
+impl fmt::Display for TimeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.denomi)
+    }
+}
+
+/// One bar of the chart. Its ticks split it into equal parts, as many as
+/// its resolution, so tick `t` sits `t / resolution` of the way through it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Measure {
+    pub resolution: usize,
+    pub signature: TimeSignature,
+}
+
+impl Measure {
+    pub fn new() -> Measure {
+        Measure::with_resolution(TICKS_PER_MEASURE)
+    }
+
+    /// A 4/4 measure split into `resolution` ticks.
+    pub fn with_resolution(resolution: usize) -> Measure {
+        Measure {
+            resolution,
+            signature: TimeSignature::COMMON,
+        }
+    }
+
+    /// How far through the measure `tick` is, from 0 up to 1.
+    pub fn position(&self, tick: usize) -> Rational {
+        Rational::new(tick as i128, self.resolution as i128)
+    }
+}
+
+impl Default for Measure {
+    fn default() -> Self {
+        Measure::new()
+    }
+}
+
+/// A note at the measure and tick it is written on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimedEvent {
+    pub measure: usize,
+    pub tick: usize,
+    pub event: NoteEvent,
+}
+
+impl TimedEvent {
+    pub fn position(&self) -> (usize, usize) {
+        (self.measure, self.tick)
+    }
+}
+
+/// Every note of a chart in one list, ordered by position. Notes on the
+/// same tick keep the order they were added in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventList {
+    events: Vec<TimedEvent>,
+}
+
+impl EventList {
+    /// Adds a note after any already on its tick. Adding notes in order
+    /// only ever appends.
+    pub fn insert(&mut self, measure: usize, tick: usize, event: NoteEvent) {
+        let index = self
+            .events
+            .partition_point(|other| other.position() <= (measure, tick));
+        self.events.insert(
+            index,
+            TimedEvent {
+                measure,
+                tick,
+                event,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, TimedEvent> {
+        self.events.iter()
+    }
+
+    /// The notes of each tick that has any, in order.
+    pub fn ticks(&self) -> impl Iterator<Item = &[TimedEvent]> {
+        self.events.chunk_by(|a, b| a.position() == b.position())
+    }
+
+    /// Mutable access to each tick's notes, for changing notes without
+    /// moving them.
+    pub(crate) fn ticks_mut(&mut self) -> impl Iterator<Item = &mut [TimedEvent]> {
+        self.events.chunk_by_mut(|a, b| a.position() == b.position())
+    }
+
+    /// The notes strictly after `start` and strictly before `end`.
+    pub fn between(&self, start: (usize, usize), end: (usize, usize)) -> &[TimedEvent] {
+        let first = self.events.partition_point(|event| event.position() <= start);
+        let last = self.events.partition_point(|event| event.position() < end);
+        &self.events[first..last.max(first)]
+    }
+}
+
+/// Sorts the notes by position, keeping notes on one tick in the order
+/// they come.
+impl FromIterator<TimedEvent> for EventList {
+    fn from_iter<I: IntoIterator<Item = TimedEvent>>(iter: I) -> EventList {
+        let mut events: Vec<TimedEvent> = iter.into_iter().collect();
+        events.sort_by_key(TimedEvent::position);
+        EventList { events }
+    }
+}
+
+impl<'a> IntoIterator for &'a EventList {
+    type Item = &'a TimedEvent;
+    type IntoIter = std::slice::Iter<'a, TimedEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.iter()
+    }
+}
+
+/// A single difficulty of a song: header metadata, its tempo map, one
+/// `Measure` per bar, and its notes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chart {
+    pub header: Header,
+    pub tempo: TempoMap,
+    pub measures: Vec<Measure>,
+    pub events: EventList,
+}
@@ -0,0 +1,276 @@
+use xml_builder::XMLElement;
+
+use crate::chart::{Chart, TimeSignature};
+use crate::error::{DrsWriteError, HoldError};
+use crate::hold::Hold;
+use crate::note::NoteEvent;
+use crate::timing::Timeline;
+use crate::xml::{add_s32_element, add_s64_element, render};
+
+/// One `<point>` of a hold's `long_point`.
+pub(crate) struct LongPoint {
+    pub time: i64,
+    pub pos_left: i32,
+    pub pos_right: i32,
+    /// `pos_lend` and `pos_rend`, where a skid moves the hold to.
+    pub skid_end: Option<(i32, i32)>,
+}
+
+/// One `<step>` of `sequence_data`, with times and positions worked out.
+pub(crate) struct Step {
+    pub stime_ms: i64,
+    pub etime_ms: i64,
+    pub stime_dt: i64,
+    pub etime_dt: i64,
+    /// 0 for steps, jumps and downs, 1 for holds.
+    pub category: i32,
+    pub pos_left: i32,
+    pub pos_right: i32,
+    /// 1 left, 2 right, 3 down, 4 jump.
+    pub kind: i32,
+    pub player_id: i32,
+    pub long_point: Vec<LongPoint>,
+}
+
+/// Adds an `s32` time element, failing if `value` doesn't fit in one.
+fn add_s32_time(xml: &mut XMLElement, name: &'static str, value: i64) -> Result<(), DrsWriteError> {
+    let value = i32::try_from(value).map_err(|_| DrsWriteError::TimeOutOfRange {
+        element: name,
+        value,
+    })?;
+    add_s32_element(xml, name, value);
+    Ok(())
+}
+
+fn xml_boilerplate(chart: &Chart, timeline: &Timeline) -> Result<XMLElement, DrsWriteError> {
+    let mut xml = XMLElement::new("data");
+
+    add_s32_element(&mut xml, "seq_version", 8);
+
+    let mut info = XMLElement::new("info");
+
+    add_s32_element(&mut info, "tick", 480);
+
+    let mut bpm_info = XMLElement::new("bpm_info");
+    for (index, change) in chart.tempo.changes().iter().enumerate() {
+        // The starting tempo also covers the padding bars and offset before
+        // the chart's first measure.
+        let (time, delta_time) = if index == 0 {
+            (0, 0)
+        } else {
+            (
+                timeline.ms_at_position(change.measure, change.position),
+                timeline.dt_at_position(change.measure, change.position),
+            )
+        };
+        let mut bpm = XMLElement::new("bpm");
+        add_s32_time(&mut bpm, "time", time)?;
+        add_s32_time(&mut bpm, "delta_time", delta_time)?;
+        add_s32_element(&mut bpm, "bpm", change.bpm.0 as i32);
+        bpm_info.add_child(bpm).unwrap();
+    }
+    info.add_child(bpm_info).unwrap();
+
+    let mut measure_info = XMLElement::new("measure_info");
+
+    let mut signatures: Vec<TimeSignature> =
+        chart.measures.iter().map(|measure| measure.signature).collect();
+    if signatures.is_empty() {
+        signatures.push(TimeSignature::COMMON);
+    }
+    for (measure_num, signature) in signatures.iter().enumerate() {
+        if measure_num > 0 && signatures[measure_num - 1] == *signature {
+            continue;
+        }
+        let mut measure = XMLElement::new("measure");
+        add_s32_time(&mut measure, "time", timeline.ms_at(measure_num, 0))?;
+        add_s32_time(&mut measure, "delta_time", timeline.dt_at(measure_num, 0))?;
+        add_s32_element(&mut measure, "num", signature.num as i32);
+        add_s32_element(&mut measure, "denomi", signature.denomi as i32);
+        measure_info.add_child(measure).unwrap();
+    }
+
+    info.add_child(measure_info).unwrap();
+
+    xml.add_child(info).unwrap();
+
+    Ok(xml)
+}
+
+/// Span of `lane`/`width` in position units, 4096 to a lane.
+fn span(lane: u8, width: u8) -> (i32, i32) {
+    (lane as i32 * 4096, (lane + width) as i32 * 4096)
+}
+
+/// Works out a hold's `long_point`, one point per waypoint and end.
+fn long_points(hold: &Hold, timeline: &Timeline) -> Vec<LongPoint> {
+    let (mut last_left, mut last_right) = match hold.start {
+        NoteEvent::LeftHoldStart { lane, width, .. }
+        | NoteEvent::RightHoldStart { lane, width, .. } => span(lane, width),
+        _ => unreachable!("holds open with a hold start"),
+    };
+    let mut points = Vec::new();
+    for point in &hold.points {
+        let time = timeline.ms_at(point.measure, point.tick);
+        let long_point = match point.event {
+            NoteEvent::SlideWaypoint { lane, width, .. }
+            | NoteEvent::SlideEnd { lane, width, .. } => {
+                let (pos_left, pos_right) = span(lane, width);
+                LongPoint {
+                    time,
+                    pos_left,
+                    pos_right,
+                    skid_end: None,
+                }
+            }
+            NoteEvent::SimpleSkidWaypoint { lane, width, .. }
+            | NoteEvent::SimpleSkidEnd { lane, width, .. } => {
+                let (pos_left, pos_right) = span(lane, width);
+                let skid_end = if let NoteEvent::SimpleSkidEnd { .. } = point.event {
+                    // The last skid ends on the half of the span it moves
+                    // towards.
+                    if pos_right > last_right {
+                        ((pos_left + pos_right) / 2, pos_right)
+                    } else {
+                        (pos_left, (pos_left + pos_right) / 2)
+                    }
+                } else {
+                    (pos_left, pos_right)
+                };
+                let long_point = LongPoint {
+                    time,
+                    pos_left: last_left,
+                    pos_right: last_right,
+                    skid_end: Some(skid_end),
+                };
+                (last_left, last_right) = (pos_left, pos_right);
+                long_point
+            }
+            NoteEvent::ComplexSkidWaypoint {
+                lane_start,
+                width_start,
+                lane_end,
+                width_end,
+                ..
+            }
+            | NoteEvent::ComplexSkidEnd {
+                lane_start,
+                width_start,
+                lane_end,
+                width_end,
+                ..
+            } => {
+                let (pos_left, pos_right) = span(lane_start, width_start);
+                let skid_end = span(lane_end, width_end);
+                (last_left, last_right) = skid_end;
+                LongPoint {
+                    time,
+                    pos_left,
+                    pos_right,
+                    skid_end: Some(skid_end),
+                }
+            }
+            _ => unreachable!("hold points are only waypoints and ends"),
+        };
+        points.push(long_point);
+    }
+    points
+}
+
+fn step_element(step: &Step) -> Result<XMLElement, DrsWriteError> {
+    let mut element = XMLElement::new("step");
+    add_s64_element(&mut element, "stime_ms", step.stime_ms);
+    add_s64_element(&mut element, "etime_ms", step.etime_ms);
+    add_s32_time(&mut element, "stime_dt", step.stime_dt)?;
+    add_s32_time(&mut element, "etime_dt", step.etime_dt)?;
+    add_s32_element(&mut element, "category", step.category);
+    add_s32_element(&mut element, "pos_left", step.pos_left);
+    add_s32_element(&mut element, "pos_right", step.pos_right);
+    add_s32_element(&mut element, "kind", step.kind);
+    add_s32_element(&mut element, "var", 0);
+    add_s32_element(&mut element, "player_id", step.player_id);
+    if step.category == 1 {
+        let mut long_point = XMLElement::new("long_point");
+        for point in &step.long_point {
+            let mut element = XMLElement::new("point");
+            add_s64_element(&mut element, "point_time", point.time);
+            add_s32_element(&mut element, "pos_left", point.pos_left);
+            add_s32_element(&mut element, "pos_right", point.pos_right);
+            if let Some((pos_lend, pos_rend)) = point.skid_end {
+                add_s32_element(&mut element, "pos_lend", pos_lend);
+                add_s32_element(&mut element, "pos_rend", pos_rend);
+            }
+            long_point.add_child(element).unwrap();
+        }
+        element.add_child(long_point).unwrap();
+    }
+    Ok(element)
+}
+
+impl Chart {
+    /// Every `<step>` the chart's DRS XML holds, in order.
+    pub(crate) fn drs_steps(&self, timeline: &Timeline) -> Result<Vec<Step>, HoldError> {
+        let holds = self.holds()?;
+        // Holds come back in the order they start, which is the order their
+        // start notes are visited in.
+        let mut holds = holds.iter();
+        let mut steps = Vec::new();
+        for note in &self.events {
+            let time = timeline.ms_at(note.measure, note.tick);
+            let time_dt = timeline.dt_at(note.measure, note.tick);
+            let step = |category, (pos_left, pos_right), kind, player_id| Step {
+                stime_ms: time,
+                etime_ms: time,
+                stime_dt: time_dt,
+                etime_dt: time_dt,
+                category,
+                pos_left,
+                pos_right,
+                kind,
+                player_id,
+                long_point: Vec::new(),
+            };
+            steps.push(match note.event {
+                NoteEvent::LeftStep { lane, width } => step(0, span(lane, width), 1, 0),
+                NoteEvent::RightStep { lane, width } => step(0, span(lane, width), 2, 0),
+                NoteEvent::Down => step(0, (0, 65536), 3, 4),
+                NoteEvent::Jump => step(0, (0, 65536), 4, 4),
+                NoteEvent::LeftHoldStart { lane, width, .. }
+                | NoteEvent::RightHoldStart { lane, width, .. } => {
+                    let hold = holds.next().unwrap();
+                    let end = hold.end();
+                    let kind = match note.event {
+                        NoteEvent::LeftHoldStart { .. } => 1,
+                        _ => 2,
+                    };
+                    Step {
+                        etime_ms: timeline.ms_at(end.measure, end.tick),
+                        etime_dt: timeline.dt_at(end.measure, end.tick),
+                        long_point: long_points(hold, timeline),
+                        ..step(1, span(lane, width), kind, 0)
+                    }
+                }
+                _ => continue,
+            });
+        }
+        Ok(steps)
+    }
+
+    /// Renders the chart as a DRS sequence XML document.
+    ///
+    /// Fails if a hold start, waypoint or end can't be paired up, or if a
+    /// time stored as an `s32` doesn't fit in one.
+    pub fn to_drs_xml(&self) -> Result<String, DrsWriteError> {
+        let timeline = Timeline::new(self);
+        let steps = self.drs_steps(&timeline)?;
+        let mut data = xml_boilerplate(self, &timeline)?;
+
+        let mut sequence_data = XMLElement::new("sequence_data");
+        for step in &steps {
+            sequence_data.add_child(step_element(step)?).unwrap();
+        }
+        data.add_child(sequence_data).unwrap();
+
+        Ok(render(data))
+    }
+}
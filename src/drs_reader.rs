@@ -0,0 +1,552 @@
+use std::str::FromStr;
+
+use roxmltree::{Document, Node};
+
+use crate::chart::{Chart, Measure, TimeSignature, TimedEvent, MAX_MEASURES, TICKS_PER_MEASURE};
+use crate::error::DrsReadError;
+use crate::note::NoteEvent;
+use crate::rational::{gcd, Rational};
+use crate::tempo::{Bpm, TempoMap};
+use crate::timing::Timeline;
+
+/// A measure number and how far through that measure, from 0 up to 1.
+type Position = (usize, Rational);
+
+/// Grids a time is looked for on, as multiples of the usual 192 ticks,
+/// coarsest first. The finest has a tick for every delta-time unit of a 4/4
+/// measure.
+const GRID_MULTIPLES: [usize; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &'static str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn value<T: FromStr>(node: Node, name: &'static str) -> Result<T, DrsReadError> {
+    let element = child(node, name).ok_or(DrsReadError::MissingElement(name))?;
+    let text = element.text().unwrap_or("").trim();
+    text.parse().map_err(|_| DrsReadError::InvalidValue {
+        element: name,
+        value: text.to_string(),
+    })
+}
+
+/// Reads a `time` or `delta_time`, which the game stores as an `s32`.
+fn time_value(node: Node, name: &'static str) -> Result<f64, DrsReadError> {
+    let time: f64 = value(node, name)?;
+    if !(i32::MIN as f64..=i32::MAX as f64).contains(&time) {
+        let text = child(node, name).and_then(|element| element.text());
+        return Err(DrsReadError::InvalidValue {
+            element: name,
+            value: text.unwrap_or("").trim().to_string(),
+        });
+    }
+    Ok(time)
+}
+
+/// Turns a measure number worked out from a time into a `usize`, if the
+/// chart can have that many measures.
+fn measure_number(measure: f64) -> Result<usize, DrsReadError> {
+    if measure >= MAX_MEASURES as f64 {
+        return Err(DrsReadError::TooManyMeasures);
+    }
+    Ok(measure.max(0_f64) as usize)
+}
+
+struct BpmEntry {
+    time: f64,
+    delta_time: f64,
+    bpm: u32,
+}
+
+struct MeasureEntry {
+    time: f64,
+    delta_time: f64,
+    signature: TimeSignature,
+    /// Number of the first measure this entry covers.
+    first_measure: usize,
+}
+
+/// Maps DRS times back onto SSF measure/tick positions.
+struct Grid {
+    ticks_per_beat: f64,
+    bpms: Vec<BpmEntry>,
+    measures: Vec<MeasureEntry>,
+}
+
+impl Grid {
+    fn measure_length(&self, signature: TimeSignature) -> f64 {
+        self.ticks_per_beat * signature.beats().to_f64()
+    }
+
+    fn ms_to_dt(&self, ms: f64) -> f64 {
+        let entry = self
+            .bpms
+            .iter()
+            .rev()
+            .find(|entry| entry.time <= ms)
+            .unwrap_or(&self.bpms[0]);
+        entry.delta_time + (ms - entry.time) * entry.bpm as f64 * self.ticks_per_beat / 6000000_f64
+    }
+
+}
+
+/// Finds the position a time was written from: the tick on the coarsest of
+/// [`GRID_MULTIPLES`] that `timeline` puts at exactly `ms`, and at `dt`
+/// where the step gives its delta-time too. A time no grid hits goes to the
+/// nearest tick of the finest.
+fn locate(timeline: &Timeline, ms: i64, dt: Option<i64>) -> Result<Position, DrsReadError> {
+    let beat = timeline.beat_at_ms(Rational::integer(ms as i128));
+    let (measure, position) = timeline.position_at_beat(beat);
+    if measure >= MAX_MEASURES {
+        return Err(DrsReadError::TooManyMeasures);
+    }
+    // Whole milliseconds can put a time just across a measure line from
+    // its tick, so the measures either side are tried too.
+    let measures = [Some(measure), Some(measure + 1), measure.checked_sub(1)];
+    // Delta-times written some other way than the converter's are only a
+    // hint, so the milliseconds alone are tried next.
+    for dt in [dt, None] {
+        for multiple in GRID_MULTIPLES {
+            let resolution = (TICKS_PER_MEASURE * multiple) as i128;
+            for measure in measures.into_iter().flatten() {
+                let start = timeline.beat_at_position(measure, Rational::ZERO);
+                let length = timeline.beat_at_position(measure, Rational::integer(1)) - start;
+                let nearest = ((beat - start) / length * Rational::integer(resolution)).round();
+                for tick in [nearest, nearest - 1, nearest + 1] {
+                    if !(0..resolution).contains(&tick) {
+                        continue;
+                    }
+                    let position = Rational::new(tick, resolution);
+                    if timeline.ms_at_position(measure, position) == ms
+                        && dt.is_none_or(|dt| timeline.dt_at_position(measure, position) == dt)
+                    {
+                        return Ok((measure, position));
+                    }
+                }
+            }
+        }
+    }
+    let resolution = (TICKS_PER_MEASURE * GRID_MULTIPLES[GRID_MULTIPLES.len() - 1]) as i128;
+    let tick = (position * Rational::integer(resolution)).round();
+    if tick < 0 {
+        return Err(DrsReadError::BeforeFirstMeasure);
+    }
+    Ok(if tick == resolution {
+        (measure + 1, Rational::ZERO)
+    } else {
+        (measure, Rational::new(tick, resolution))
+    })
+}
+
+fn lane(pos: i64) -> Result<u8, DrsReadError> {
+    if pos % 4096 != 0 || !(0..16 * 4096).contains(&pos) {
+        return Err(DrsReadError::UnalignedPosition(pos));
+    }
+    Ok((pos / 4096) as u8)
+}
+
+fn width(pos_left: i64, pos_right: i64) -> Result<u8, DrsReadError> {
+    let width = pos_right - pos_left;
+    if width % 4096 != 0 || !(4096..=16 * 4096).contains(&width) {
+        return Err(DrsReadError::UnalignedPosition(pos_right));
+    }
+    Ok((width / 4096) as u8)
+}
+
+fn read_grid(info: Node) -> Result<Grid, DrsReadError> {
+    // Sequences without a `<tick>` use the usual 480 per beat, but one that
+    // is there has to make sense.
+    let ticks_per_beat = match child(info, "tick") {
+        Some(_) => value::<f64>(info, "tick")?,
+        None => 480_f64,
+    };
+    if !(ticks_per_beat.is_finite() && ticks_per_beat > 0_f64) {
+        return Err(DrsReadError::InvalidValue {
+            element: "tick",
+            value: ticks_per_beat.to_string(),
+        });
+    }
+
+    let mut bpms = Vec::new();
+    if let Some(bpm_info) = child(info, "bpm_info") {
+        for bpm in bpm_info.children().filter(|node| node.has_tag_name("bpm")) {
+            let entry = BpmEntry {
+                time: time_value(bpm, "time")?,
+                delta_time: time_value(bpm, "delta_time")?,
+                bpm: value(bpm, "bpm")?,
+            };
+            if entry.bpm == 0 {
+                return Err(DrsReadError::InvalidValue {
+                    element: "bpm",
+                    value: entry.bpm.to_string(),
+                });
+            }
+            bpms.push(entry);
+        }
+    }
+    if bpms.is_empty() {
+        return Err(DrsReadError::MissingElement("bpm"));
+    }
+    bpms.sort_by(|a, b| a.delta_time.total_cmp(&b.delta_time));
+
+    let mut grid = Grid {
+        ticks_per_beat,
+        bpms,
+        measures: Vec::new(),
+    };
+
+    let mut entries = Vec::new();
+    if let Some(measure_info) = child(info, "measure_info") {
+        for measure in measure_info
+            .children()
+            .filter(|node| node.has_tag_name("measure"))
+        {
+            let time = time_value(measure, "time")?;
+            let delta_time = match child(measure, "delta_time") {
+                Some(_) => time_value(measure, "delta_time")?,
+                None => grid.ms_to_dt(time),
+            };
+            let signature = TimeSignature {
+                num: value(measure, "num")?,
+                denomi: value(measure, "denomi")?,
+            };
+            if signature.num == 0 || signature.denomi == 0 {
+                return Err(DrsReadError::InvalidValue {
+                    element: "measure",
+                    value: signature.to_string(),
+                });
+            }
+            entries.push((time, delta_time, signature));
+        }
+    }
+    if entries.is_empty() {
+        entries.push((0_f64, 0_f64, TimeSignature::COMMON));
+    }
+    entries.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut first_measure = 0;
+    for (index, (time, delta_time, signature)) in entries.iter().enumerate() {
+        if index > 0 {
+            let (_, previous_dt, previous_signature) = entries[index - 1];
+            let length = grid.measure_length(previous_signature);
+            let measures = ((delta_time - previous_dt) / length).round();
+            first_measure = measure_number(first_measure as f64 + measures)?;
+        }
+        grid.measures.push(MeasureEntry {
+            time: *time,
+            delta_time: *delta_time,
+            signature: *signature,
+            first_measure,
+        });
+    }
+
+    Ok(grid)
+}
+
+/// Most whole bars a `WAVEOFFSET` is looked for in, past the first one.
+const MAX_OFFSET_BARS: usize = 100;
+
+/// Splits the time before the first measure into whole padding bars and a
+/// `WAVEOFFSET`, both at the starting tempo.
+///
+/// The offset is whole milliseconds, so most splits only come close to the
+/// first measure's time. Where one gives back exactly the `time` and
+/// `delta_time` this converter would write, that one is used, so charts
+/// survive a trip through DRS XML unchanged. Otherwise the offset is what's
+/// left after the whole bars.
+fn lead_in(grid: &Grid, first: &MeasureEntry) -> (u32, i32) {
+    let bpm = grid.bpms[0].bpm;
+    let ms_per_beat = Rational::new(6000000, bpm as i128);
+    let bar_beats = first.signature.beats();
+    let bar_ms = bar_beats * ms_per_beat;
+    let ticks_per_beat = Rational::integer(grid.ticks_per_beat.round() as i128);
+    let (time, delta_time) = (first.time.round() as i128, first.delta_time.round() as i128);
+
+    let bars = (first.delta_time / grid.measure_length(first.signature)).floor().max(0_f64) as u32;
+    let split = |padding_bars: u32| {
+        let padding_ms = Rational::integer(padding_bars as i128) * bar_ms;
+        let offset_ms = (Rational::integer(time) - padding_ms).round();
+        (padding_bars, offset_ms, padding_ms)
+    };
+    // A negative offset leaves the first measure short of a whole number of
+    // bars, and one of more than a bar leaves fewer padding bars, so other
+    // counts are tried after the whole bars before the first measure.
+    let counts = [bars, bars + 1]
+        .into_iter()
+        .chain((0..bars).rev().take(MAX_OFFSET_BARS));
+    for padding_bars in counts {
+        let (_, nearest_ms, padding_ms) = split(padding_bars);
+        for offset_ms in [nearest_ms, nearest_ms - 1, nearest_ms + 1] {
+            let offset = Rational::integer(offset_ms);
+            let beats = Rational::integer(padding_bars as i128) * bar_beats + offset / ms_per_beat;
+            if (padding_ms + offset).round() == time
+                && (beats * ticks_per_beat).round() == delta_time
+            {
+                return (padding_bars, offset_ms as i32);
+            }
+        }
+    }
+    let (padding_bars, offset_ms, _) = split(bars);
+    (padding_bars, offset_ms as i32)
+}
+
+/// Tracks which base-36 hold ids are in use while holds are rebuilt.
+struct HoldIds {
+    /// The position each id's hold ends at.
+    open: Vec<Option<Position>>,
+}
+
+impl HoldIds {
+    fn allocate(&mut self, start: Position, end: Position) -> Result<u8, DrsReadError> {
+        // Ids count from 1 as hand-written charts do, with 0 used last. An id
+        // is only reused once its previous hold has ended before `start`.
+        let id = (1..36)
+            .chain(0..1)
+            .find(|id| self.open[*id].is_none_or(|open_end| open_end < start))
+            .ok_or(DrsReadError::TooManyHolds)?;
+        self.open[id] = Some(end);
+        Ok(id as u8)
+    }
+}
+
+struct Point {
+    position: Position,
+    pos_left: i64,
+    pos_right: i64,
+    skid_end: Option<(i64, i64)>,
+}
+
+/// Reads a step's `stime_dt` or `etime_dt`, which older sequences leave out.
+fn optional_dt(step: Node, name: &'static str) -> Result<Option<i64>, DrsReadError> {
+    match child(step, name) {
+        Some(_) => Ok(Some(time_value(step, name)?.round() as i64)),
+        None => Ok(None),
+    }
+}
+
+/// Rebuilds a chart from a DRS sequence XML document through its
+/// `bpm_info` and `measure_info`.
+///
+/// Each time goes back to the tick it converts from exactly, on the usual
+/// 192-tick grid where one does and on a finer one where not, and each
+/// measure gets the coarsest resolution its notes all land on.
+pub fn parse_drs_xml(input: &str) -> Result<Chart, DrsReadError> {
+    let document = Document::parse(input).map_err(|e| DrsReadError::Xml(e.to_string()))?;
+    let data = document.root_element();
+    let info = child(data, "info").ok_or(DrsReadError::MissingElement("info"))?;
+    let grid = read_grid(info)?;
+
+    let mut chart = Chart::default();
+
+    let first = &grid.measures[0];
+    let (padding_bars, offset_ms) = lead_in(&grid, first);
+    if padding_bars > 0 {
+        chart.header.padding_bars = Some(padding_bars);
+    }
+    if offset_ms != 0 {
+        chart.header.wave_offset = Some(offset_ms);
+    }
+
+    let last_entry = grid.measures[grid.measures.len() - 1].first_measure;
+    chart.measures = vec![Measure::new(); last_entry + 1];
+    for entry in &grid.measures {
+        for measure in &mut chart.measures[entry.first_measure..] {
+            measure.signature = entry.signature;
+        }
+    }
+
+    // Each tempo change is found on the timeline of the ones before it.
+    chart.tempo = TempoMap::new(Bpm(grid.bpms[0].bpm));
+    for entry in &grid.bpms[1..] {
+        let timeline = Timeline::new(&chart);
+        let (ms, dt) = (entry.time.round() as i64, entry.delta_time.round() as i64);
+        let (measure, position) = locate(&timeline, ms, Some(dt))?;
+        chart.tempo.insert(measure, position, Bpm(entry.bpm));
+    }
+    let timeline = Timeline::new(&chart);
+
+    let mut steps = Vec::new();
+    if let Some(sequence_data) = child(data, "sequence_data") {
+        for step in sequence_data
+            .children()
+            .filter(|node| node.has_tag_name("step"))
+        {
+            steps.push((value::<i64>(step, "stime_ms")?, step));
+        }
+    }
+    steps.sort_by_key(|(stime_ms, _)| *stime_ms);
+
+    let mut notes = Vec::<(Position, NoteEvent)>::new();
+    let mut hold_ids = HoldIds {
+        open: vec![None; 36],
+    };
+
+    for (stime_ms, step) in steps {
+        let start = locate(&timeline, stime_ms, optional_dt(step, "stime_dt")?)?;
+        let category: i32 = value(step, "category")?;
+        let kind: i32 = value(step, "kind")?;
+        let pos_left: i64 = value(step, "pos_left")?;
+        let pos_right: i64 = value(step, "pos_right")?;
+
+        match (category, kind) {
+            (0, 1) | (0, 2) => {
+                let (lane, width) = (lane(pos_left)?, width(pos_left, pos_right)?);
+                let note = if kind == 1 {
+                    NoteEvent::LeftStep { lane, width }
+                } else {
+                    NoteEvent::RightStep { lane, width }
+                };
+                notes.push((start, note));
+            }
+            (0, 3) => notes.push((start, NoteEvent::Down)),
+            (0, 4) => notes.push((start, NoteEvent::Jump)),
+            (1, 1) | (1, 2) => {
+                let mut points = Vec::new();
+                if let Some(long_point) = child(step, "long_point") {
+                    for point in long_point
+                        .children()
+                        .filter(|node| node.has_tag_name("point"))
+                    {
+                        let skid_end = match child(point, "pos_lend") {
+                            Some(_) => Some((value(point, "pos_lend")?, value(point, "pos_rend")?)),
+                            None => None,
+                        };
+                        points.push(Point {
+                            position: locate(&timeline, value(point, "point_time")?, None)?,
+                            pos_left: value(point, "pos_left")?,
+                            pos_right: value(point, "pos_right")?,
+                            skid_end,
+                        });
+                    }
+                }
+                if points.is_empty() {
+                    points.push(Point {
+                        position: locate(
+                            &timeline,
+                            value(step, "etime_ms")?,
+                            optional_dt(step, "etime_dt")?,
+                        )?,
+                        pos_left,
+                        pos_right,
+                        skid_end: None,
+                    });
+                }
+
+                let end = points.last().unwrap().position;
+                let id = hold_ids.allocate(start, end)?;
+                let (lane_start, width_start) = (lane(pos_left)?, width(pos_left, pos_right)?);
+                notes.push((
+                    start,
+                    if kind == 1 {
+                        NoteEvent::LeftHoldStart {
+                            id,
+                            lane: lane_start,
+                            width: width_start,
+                        }
+                    } else {
+                        NoteEvent::RightHoldStart {
+                            id,
+                            lane: lane_start,
+                            width: width_start,
+                        }
+                    },
+                ));
+
+                let (mut last_left, mut last_right) = (pos_left, pos_right);
+                let point_count = points.len();
+                for (index, point) in points.into_iter().enumerate() {
+                    let is_final = index + 1 == point_count;
+                    let note = match point.skid_end {
+                        None => {
+                            let (lane, width) =
+                                (lane(point.pos_left)?, width(point.pos_left, point.pos_right)?);
+                            last_left = point.pos_left;
+                            last_right = point.pos_right;
+                            if is_final {
+                                NoteEvent::SlideEnd { id, lane, width }
+                            } else {
+                                NoteEvent::SlideWaypoint { id, lane, width }
+                            }
+                        }
+                        Some((pos_lend, pos_rend)) => {
+                            let aligned = pos_lend % 4096 == 0 && pos_rend % 4096 == 0;
+                            let continues = (point.pos_left, point.pos_right) == (last_left, last_right);
+                            let note = if is_final && !aligned && continues {
+                                // A simple skid end only keeps half of the
+                                // end lane's width, on the side it moved to.
+                                let (left, right) = if pos_rend > last_right {
+                                    (2 * pos_lend - pos_rend, pos_rend)
+                                } else {
+                                    (pos_lend, 2 * pos_rend - pos_lend)
+                                };
+                                NoteEvent::SimpleSkidEnd {
+                                    id,
+                                    lane: lane(left)?,
+                                    width: width(left, right)?,
+                                }
+                            } else if !is_final && continues {
+                                NoteEvent::SimpleSkidWaypoint {
+                                    id,
+                                    lane: lane(pos_lend)?,
+                                    width: width(pos_lend, pos_rend)?,
+                                }
+                            } else {
+                                let (lane_start, width_start) =
+                                    (lane(point.pos_left)?, width(point.pos_left, point.pos_right)?);
+                                let (lane_end, width_end) = (lane(pos_lend)?, width(pos_lend, pos_rend)?);
+                                if is_final {
+                                    NoteEvent::ComplexSkidEnd {
+                                        id,
+                                        lane_start,
+                                        width_start,
+                                        lane_end,
+                                        width_end,
+                                    }
+                                } else {
+                                    NoteEvent::ComplexSkidWaypoint {
+                                        id,
+                                        lane_start,
+                                        width_start,
+                                        lane_end,
+                                        width_end,
+                                    }
+                                }
+                            };
+                            last_left = pos_lend;
+                            last_right = pos_rend;
+                            note
+                        }
+                    };
+                    notes.push((point.position, note));
+                }
+            }
+            _ => return Err(DrsReadError::UnknownStep { category, kind }),
+        }
+    }
+
+    // Each measure keeps the usual grid unless a note falls between its
+    // ticks, in which case it gets the coarsest finer grid they all land on.
+    let last_measure = notes
+        .iter()
+        .map(|((measure, _), _)| *measure)
+        .chain(chart.tempo.changes().iter().map(|change| change.measure))
+        .fold(last_entry, usize::max);
+    let trailing = chart.measures[last_entry].clone();
+    chart.measures.resize(last_measure + 1, trailing);
+    for ((measure, position), _) in &notes {
+        let resolution = chart.measures[*measure].resolution as i128;
+        chart.measures[*measure].resolution =
+            (resolution / gcd(resolution, position.denom()) * position.denom()) as usize;
+    }
+    chart.events = notes
+        .into_iter()
+        .map(|((measure, position), event)| TimedEvent {
+            measure,
+            tick: (position * Rational::integer(chart.measures[measure].resolution as i128))
+                .numer() as usize,
+            event,
+        })
+        .collect();
+
+    Ok(chart)
+}
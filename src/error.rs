@@ -0,0 +1,521 @@
+use std::fmt;
+
+use crate::chart::Difficulty;
+use crate::hold::hold_id;
+use crate::note::NoteEvent;
+use crate::rational::Rational;
+use crate::tempo::Bpm;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnexpectedEof,
+    UnknownNoteType(char),
+    MissingDigit(&'static str),
+    InvalidDigit(&'static str),
+    TickOutOfRange { tick: usize, ticks_per_measure: usize },
+    MeasureOutOfRange { measure: usize, max: usize },
+    InvalidDifficulty,
+    InvalidNumber,
+    NoteBeforeMeasure,
+    InvalidBpm,
+    UndefinedBpm,
+    MissingInitialBpm,
+    InvalidChannelData,
+    InvalidMeasureLength,
+    InvalidResolution,
+    TrailingCharacters,
+    UnrecognizedLine,
+    DataBeforeStart,
+    CommandAfterStart,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedEof => write!(f, "unexpected end of file in header"),
+            ParseErrorKind::UnknownNoteType(note_type) => {
+                write!(f, "unknown note type '{}'", note_type)
+            }
+            ParseErrorKind::MissingDigit(field) => write!(f, "missing {} digit", field),
+            ParseErrorKind::InvalidDigit(field) => write!(f, "invalid {} digit", field),
+            ParseErrorKind::TickOutOfRange {
+                tick,
+                ticks_per_measure,
+            } => write!(
+                f,
+                "tick {} out of range for {}-tick measure",
+                tick, ticks_per_measure
+            ),
+            ParseErrorKind::MeasureOutOfRange { measure, max } => {
+                write!(f, "measure {} out of range, the last allowed is {}", measure, max)
+            }
+            ParseErrorKind::InvalidDifficulty => write!(f, "difficulty must be 0, 1 or 2"),
+            ParseErrorKind::InvalidNumber => write!(f, "invalid number"),
+            ParseErrorKind::NoteBeforeMeasure => write!(f, "notes before the first measure number"),
+            ParseErrorKind::InvalidBpm => write!(f, "invalid BPM"),
+            ParseErrorKind::UndefinedBpm => write!(f, "BPM is not defined in the header"),
+            ParseErrorKind::MissingInitialBpm => write!(f, "missing #BPM01: definition"),
+            ParseErrorKind::InvalidChannelData => {
+                write!(f, "channel data must be pairs of base-36 digits")
+            }
+            ParseErrorKind::InvalidMeasureLength => {
+                write!(f, "measure length must be a beat count or a time signature")
+            }
+            ParseErrorKind::InvalidResolution => {
+                write!(f, "measure resolution must be at least 1 tick")
+            }
+            ParseErrorKind::TrailingCharacters => write!(f, "unexpected characters after note"),
+            ParseErrorKind::UnrecognizedLine => write!(
+                f,
+                "expected a #command, a measure number, a tick:notes line or END"
+            ),
+            ParseErrorKind::DataBeforeStart => write!(f, "measure data before #START"),
+            ParseErrorKind::CommandAfterStart => write!(f, "header command after #START"),
+        }
+    }
+}
+
+/// An error in an SSF source, located by 1-based line and column.
+///
+/// `token` is the offending piece of text, starting at `column`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn new(line: usize, column: usize, token: &str, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line,
+            column,
+            token: token.to_string(),
+            kind,
+        }
+    }
+
+    /// Moves an error reported against a single token onto the line and
+    /// column where that token starts in the full source.
+    pub(crate) fn offset(mut self, line: usize, column: usize) -> ParseError {
+        self.line += line - 1;
+        self.column += column - 1;
+        self
+    }
+
+    /// Renders the error followed by the offending source line with the
+    /// token underlined by carets.
+    pub fn snippet(&self, source: &str) -> String {
+        let mut output = format!("{}", self);
+        if let Some(line) = source.lines().nth(self.line - 1) {
+            let gutter = self.line.to_string();
+            let underline = self.token.chars().count().max(1);
+            output += &format!(
+                "\n{} |\n{} | {}\n{} | {}{}",
+                " ".repeat(gutter.len()),
+                gutter,
+                line,
+                " ".repeat(gutter.len()),
+                " ".repeat(self.column - 1),
+                "^".repeat(underline)
+            );
+        }
+        output
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.kind)?;
+        if !self.token.is_empty() {
+            write!(f, " (at '{}')", self.token)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataError {
+    MissingSongId { title: Option<String> },
+    /// A SONGID that can't be used as a file name as it is.
+    InvalidSongId { song_id: String },
+    MissingDifficulty { song_id: String },
+    DuplicateDifficulty { song_id: String, difficulty: Difficulty },
+    InvalidLevel { song_id: String, level: String },
+    /// Two difficulties of one song disagree on a field they must share.
+    Mismatch {
+        song_id: String,
+        field: &'static str,
+        first: String,
+        other: String,
+    },
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::MissingSongId { title: Some(title) } => {
+                write!(f, "chart '{}' has no SONGID", title)
+            }
+            MetadataError::MissingSongId { title: None } => write!(f, "chart has no SONGID"),
+            MetadataError::InvalidSongId { song_id } => write!(
+                f,
+                "SONGID '{}' must be a plain file name, without slashes or '..'",
+                song_id
+            ),
+            MetadataError::MissingDifficulty { song_id } => {
+                write!(f, "song {}: chart has no DIFFICULTY", song_id)
+            }
+            MetadataError::DuplicateDifficulty {
+                song_id,
+                difficulty,
+            } => write!(f, "song {}: more than one {} chart", song_id, difficulty),
+            MetadataError::InvalidLevel { song_id, level } => {
+                write!(f, "song {}: PLAYLEVEL '{}' is not a number", song_id, level)
+            }
+            MetadataError::Mismatch {
+                song_id,
+                field,
+                first,
+                other,
+            } => write!(
+                f,
+                "song {}: charts disagree on {} ('{}' and '{}')",
+                song_id, field, first, other
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    /// `#00008:` is the padding bar count, so measure 0 cannot hold a tempo
+    /// change.
+    TempoChangeInFirstMeasure { position: Rational },
+    /// A tempo or time signature change in a measure the header's
+    /// three-digit `#mmmxx:` channels can't name.
+    ChangeOutOfRange { measure: usize, max: usize },
+    UnencodableNote {
+        measure: usize,
+        tick: usize,
+        note: NoteEvent,
+    },
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::TempoChangeInFirstMeasure { position } => write!(
+                f,
+                "tempo change {} of the way through measure 0 cannot be written to SSF",
+                position
+            ),
+            WriteError::ChangeOutOfRange { measure, max } => write!(
+                f,
+                "change in measure {} cannot be written to SSF, the last allowed is {}",
+                measure, max
+            ),
+            WriteError::UnencodableNote {
+                measure,
+                tick,
+                note,
+            } => write!(
+                f,
+                "measure {}, tick {}: {:?} has no SSF encoding",
+                measure, tick, note
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// Where a hold id is used, for hold chains that don't pair up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HoldError {
+    /// A waypoint whose id has no open hold.
+    OrphanWaypoint { measure: usize, tick: usize, id: u8 },
+    /// An end whose id has no open hold.
+    OrphanEnd { measure: usize, tick: usize, id: u8 },
+    /// A hold that starts here but never ends, either because the chart
+    /// runs out or because its id is started again first.
+    Unterminated { measure: usize, tick: usize, id: u8 },
+}
+
+impl HoldError {
+    /// The measure and tick the error points at.
+    pub fn position(&self) -> (usize, usize) {
+        match *self {
+            HoldError::OrphanWaypoint { measure, tick, .. }
+            | HoldError::OrphanEnd { measure, tick, .. }
+            | HoldError::Unterminated { measure, tick, .. } => (measure, tick),
+        }
+    }
+
+    /// What is wrong, without the position, for reports that give the
+    /// position themselves.
+    pub fn reason(&self) -> String {
+        match *self {
+            HoldError::OrphanWaypoint { id, .. } => {
+                format!("waypoint for hold {} with no open hold", hold_id(id))
+            }
+            HoldError::OrphanEnd { id, .. } => {
+                format!("end of hold {} with no open hold", hold_id(id))
+            }
+            HoldError::Unterminated { id, .. } => format!("hold {} never ends", hold_id(id)),
+        }
+    }
+}
+
+impl fmt::Display for HoldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (measure, tick) = self.position();
+        write!(f, "measure {}, tick {}: {}", measure, tick, self.reason())
+    }
+}
+
+impl std::error::Error for HoldError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransformError {
+    /// A note the transform would move off the pad.
+    LaneOutOfRange {
+        measure: usize,
+        tick: usize,
+        note: NoteEvent,
+    },
+    Hold(HoldError),
+    /// A rate that isn't a positive number.
+    InvalidRate(Rational),
+    /// A tempo the rate takes past what a chart can hold.
+    BpmOutOfRange { measure: usize, position: Rational },
+    /// A rate that would give `bpm` more than two decimal places.
+    InexactRate { rate: Rational, bpm: Bpm },
+    /// A `WAVEOFFSET` the rate takes past what a chart can hold.
+    OffsetOutOfRange,
+}
+
+impl From<HoldError> for TransformError {
+    fn from(error: HoldError) -> TransformError {
+        TransformError::Hold(error)
+    }
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformError::LaneOutOfRange {
+                measure,
+                tick,
+                note,
+            } => write!(
+                f,
+                "measure {}, tick {}: {:?} would leave the pad",
+                measure, tick, note
+            ),
+            TransformError::Hold(error) => error.fmt(f),
+            TransformError::InvalidRate(rate) => write!(f, "rate {} is not above 0", rate),
+            TransformError::BpmOutOfRange { measure, position } => write!(
+                f,
+                "measure {} at {}: scaled tempo is out of range",
+                measure, position
+            ),
+            TransformError::InexactRate { rate, bpm } => write!(
+                f,
+                "rate {} makes {} BPM a tempo finer than 0.01 BPM",
+                rate, bpm
+            ),
+            TransformError::OffsetOutOfRange => write!(f, "scaled WAVEOFFSET is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for TransformError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrsWriteError {
+    Hold(HoldError),
+    /// A time too far from the start of the audio for the `s32` element
+    /// DRS XML keeps it in.
+    TimeOutOfRange { element: &'static str, value: i64 },
+}
+
+impl From<HoldError> for DrsWriteError {
+    fn from(error: HoldError) -> DrsWriteError {
+        DrsWriteError::Hold(error)
+    }
+}
+
+impl fmt::Display for DrsWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrsWriteError::Hold(error) => error.fmt(f),
+            DrsWriteError::TimeOutOfRange { element, value } => {
+                write!(f, "<{}> {} does not fit in an s32", element, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DrsWriteError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrsReadError {
+    Xml(String),
+    MissingElement(&'static str),
+    InvalidValue { element: &'static str, value: String },
+    /// A position that is not on a lane boundary, or outside the pad.
+    UnalignedPosition(i64),
+    BeforeFirstMeasure,
+    /// A time past the last measure a chart can have.
+    TooManyMeasures,
+    TooManyHolds,
+    UnknownStep { category: i32, kind: i32 },
+}
+
+impl fmt::Display for DrsReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrsReadError::Xml(error) => write!(f, "invalid XML: {}", error),
+            DrsReadError::MissingElement(name) => write!(f, "missing <{}> element", name),
+            DrsReadError::InvalidValue { element, value } => {
+                write!(f, "invalid <{}> value '{}'", element, value)
+            }
+            DrsReadError::UnalignedPosition(pos) => {
+                write!(f, "position {} is not on a lane boundary", pos)
+            }
+            DrsReadError::BeforeFirstMeasure => write!(f, "step before the first measure"),
+            DrsReadError::TooManyMeasures => {
+                write!(f, "step or measure past measure {}", crate::chart::MAX_MEASURES - 1)
+            }
+            DrsReadError::TooManyHolds => write!(f, "more than 36 holds at once"),
+            DrsReadError::UnknownStep { category, kind } => {
+                write!(f, "unknown step category {} kind {}", category, kind)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DrsReadError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KbinError {
+    Xml(String),
+    /// The data doesn't start with a binary XML header.
+    NotKbin,
+    UnsupportedEncoding(u8),
+    /// A `__type`, or an array of one, that has no node type here.
+    UnsupportedType(String),
+    UnknownNodeType(u8),
+    /// A node name that can't be packed into 6-bit characters.
+    InvalidName(String),
+    InvalidValue { name: String, value: String },
+    InvalidString,
+    Truncated,
+    /// Node ends that don't match the nodes they close.
+    Unbalanced,
+}
+
+impl fmt::Display for KbinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KbinError::Xml(error) => write!(f, "invalid XML: {}", error),
+            KbinError::NotKbin => write!(f, "not a binary XML file"),
+            KbinError::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported string encoding 0x{:02X}", encoding)
+            }
+            KbinError::UnsupportedType(name) => write!(f, "unsupported node type '{}'", name),
+            KbinError::UnknownNodeType(id) => write!(f, "unknown node type {}", id),
+            KbinError::InvalidName(name) => write!(f, "node name '{}' can't be compressed", name),
+            KbinError::InvalidValue { name, value } => {
+                write!(f, "invalid <{}> value '{}'", name, value)
+            }
+            KbinError::InvalidString => write!(f, "string is not valid UTF-8"),
+            KbinError::Truncated => write!(f, "unexpected end of data"),
+            KbinError::Unbalanced => write!(f, "node ends don't match their nodes"),
+        }
+    }
+}
+
+impl std::error::Error for KbinError {}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    Json(String),
+    /// A schema version this build doesn't read.
+    UnsupportedVersion(u32),
+    /// A measure with no ticks, or a time signature with a zero in it.
+    InvalidMeasure(usize),
+    TickOutOfRange { measure: usize, tick: usize },
+    /// A lane above 15, or a width outside 1 to 16.
+    InvalidLane { measure: usize, tick: usize },
+    Hold(HoldError),
+}
+
+#[cfg(feature = "serde")]
+impl From<HoldError> for JsonError {
+    fn from(error: HoldError) -> JsonError {
+        JsonError::Hold(error)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Json(error) => write!(f, "invalid JSON: {}", error),
+            JsonError::UnsupportedVersion(version) => write!(
+                f,
+                "schema version {} is not supported, expected {}",
+                version,
+                crate::json::JSON_SCHEMA_VERSION
+            ),
+            JsonError::InvalidMeasure(measure) => write!(
+                f,
+                "measure {}: resolution and time signature must be above 0",
+                measure
+            ),
+            JsonError::TickOutOfRange { measure, tick } => {
+                write!(f, "measure {}, tick {}: tick is past the end of the measure", measure, tick)
+            }
+            JsonError::InvalidLane { measure, tick } => write!(
+                f,
+                "measure {}, tick {}: lane must be 0 to 15 and width 1 to 16",
+                measure, tick
+            ),
+            JsonError::Hold(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for JsonError {}
+
+#[cfg(feature = "png")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RasterError {
+    Svg(String),
+    /// A page too big to hold in memory as pixels.
+    TooLarge,
+    Png(String),
+}
+
+#[cfg(feature = "png")]
+impl fmt::Display for RasterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RasterError::Svg(error) => write!(f, "invalid SVG: {}", error),
+            RasterError::TooLarge => write!(f, "page is too large to rasterise"),
+            RasterError::Png(error) => write!(f, "PNG encoding failed: {}", error),
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+impl std::error::Error for RasterError {}
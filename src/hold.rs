@@ -0,0 +1,164 @@
+use crate::chart::{Chart, TimedEvent};
+use crate::error::HoldError;
+use crate::note::NoteEvent;
+
+/// One hold, from its start note through every waypoint to its end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hold {
+    pub id: u8,
+    pub measure: usize,
+    pub tick: usize,
+    /// The `LeftHoldStart` or `RightHoldStart` that opened the hold.
+    pub start: NoteEvent,
+    /// Waypoints in order, with the end as the last entry.
+    pub points: Vec<TimedEvent>,
+}
+
+impl Hold {
+    pub fn end(&self) -> &TimedEvent {
+        self.points.last().unwrap()
+    }
+}
+
+/// Writes a hold id the way SSF does, as a base-36 digit.
+pub(crate) fn hold_id(id: u8) -> String {
+    match char::from_digit(id as u32, 36) {
+        Some(c) => c.to_ascii_uppercase().to_string(),
+        None => id.to_string(),
+    }
+}
+
+enum Role {
+    Start,
+    Waypoint,
+    End,
+}
+
+fn role(event: &NoteEvent) -> Option<(u8, Role)> {
+    match *event {
+        NoteEvent::LeftHoldStart { id, .. } | NoteEvent::RightHoldStart { id, .. } => {
+            Some((id, Role::Start))
+        }
+        NoteEvent::SlideWaypoint { id, .. }
+        | NoteEvent::SimpleSkidWaypoint { id, .. }
+        | NoteEvent::ComplexSkidWaypoint { id, .. } => Some((id, Role::Waypoint)),
+        NoteEvent::SlideEnd { id, .. }
+        | NoteEvent::SimpleSkidEnd { id, .. }
+        | NoteEvent::ComplexSkidEnd { id, .. } => Some((id, Role::End)),
+        _ => None,
+    }
+}
+
+/// Tracks which hold each id currently belongs to, so every waypoint and
+/// end finds its chain without searching.
+struct Chains {
+    holds: Vec<Hold>,
+    /// Index into `holds` of the open hold for each id.
+    open: Vec<Option<usize>>,
+}
+
+impl Chains {
+    fn open_hold(&self, id: u8) -> Option<usize> {
+        self.open[id as usize]
+    }
+
+    fn unterminated(&self, index: usize) -> HoldError {
+        let hold = &self.holds[index];
+        HoldError::Unterminated {
+            measure: hold.measure,
+            tick: hold.tick,
+            id: hold.id,
+        }
+    }
+
+    fn attach(&mut self, index: usize, role: Role, point: TimedEvent) {
+        let hold = &mut self.holds[index];
+        if let Role::End = role {
+            self.open[hold.id as usize] = None;
+        }
+        hold.points.push(point);
+    }
+}
+
+impl Chart {
+    /// Pairs every hold start with its waypoints and end, in the order the
+    /// holds start, in one pass over the chart's notes.
+    ///
+    /// Ids are only bound to one hold at a time, so an id can be reused once
+    /// its hold has ended. On a tick where an id both ends and starts again,
+    /// the waypoints and end belong to the hold that was already open.
+    pub fn holds(&self) -> Result<Vec<Hold>, HoldError> {
+        let mut chains = Chains {
+            holds: Vec::new(),
+            open: vec![None; u8::MAX as usize + 1],
+        };
+
+        for tick in self.events.ticks() {
+            let (measure_num, tick_num) = tick[0].position();
+
+            // Holds opened on earlier ticks take their waypoints and ends
+            // first.
+            let mut attached = vec![false; tick.len()];
+            for (index, point) in tick.iter().enumerate() {
+                if let Some((id, role @ (Role::Waypoint | Role::End))) = role(&point.event) {
+                    // Nothing has started on this tick yet, so any open
+                    // hold is an earlier one.
+                    if let Some(hold) = chains.open_hold(id) {
+                        chains.attach(hold, role, *point);
+                        attached[index] = true;
+                    }
+                }
+            }
+
+            for point in tick {
+                if let Some((id, Role::Start)) = role(&point.event) {
+                    if let Some(hold) = chains.open_hold(id) {
+                        // The id was reused before its hold ended.
+                        return Err(chains.unterminated(hold));
+                    }
+                    chains.open[id as usize] = Some(chains.holds.len());
+                    chains.holds.push(Hold {
+                        id,
+                        measure: measure_num,
+                        tick: tick_num,
+                        start: point.event,
+                        points: Vec::new(),
+                    });
+                }
+            }
+
+            // Anything left can only belong to a hold started on this tick.
+            for (index, point) in tick.iter().enumerate() {
+                if attached[index] {
+                    continue;
+                }
+                let Some((id, role)) = role(&point.event) else {
+                    continue;
+                };
+                let orphan = match role {
+                    Role::Start => continue,
+                    Role::Waypoint => HoldError::OrphanWaypoint {
+                        measure: measure_num,
+                        tick: tick_num,
+                        id,
+                    },
+                    Role::End => HoldError::OrphanEnd {
+                        measure: measure_num,
+                        tick: tick_num,
+                        id,
+                    },
+                };
+                match chains.open_hold(id) {
+                    Some(hold) => chains.attach(hold, role, *point),
+                    None => return Err(orphan),
+                }
+            }
+        }
+
+        if let Some(hold) = chains.open.iter().flatten().min() {
+            return Err(chains.unterminated(*hold));
+        }
+        Ok(chains.holds)
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use crate::chart::{Chart, Header, Measure, TimedEvent, TICKS_PER_MEASURE};
+use crate::error::{HoldError, JsonError};
+use crate::hold::Hold;
+use crate::tempo::TempoMap;
+
+/// Version of the JSON chart schema. [`Chart::to_json`] writes it and
+/// [`Chart::from_json`] only reads documents that have it, so scripts can
+/// tell when the layout changes under them.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// A chart as JSON. Steps, jumps and downs are listed in `notes`, and each
+/// hold is one entry in `holds` with its waypoints and end.
+#[derive(Serialize, Deserialize)]
+struct ChartDocument {
+    version: u32,
+    header: Header,
+    tempo: TempoMap,
+    measures: Vec<Measure>,
+    notes: Vec<TimedEvent>,
+    holds: Vec<Hold>,
+}
+
+/// Just enough of a document to check its version before reading the rest.
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+fn json_error(error: serde_json::Error) -> JsonError {
+    JsonError::Json(error.to_string())
+}
+
+impl Chart {
+    /// Writes the chart as a JSON document of schema version
+    /// [`JSON_SCHEMA_VERSION`].
+    ///
+    /// Fails if a hold start, waypoint or end can't be paired up.
+    pub fn to_json(&self) -> Result<String, HoldError> {
+        let document = ChartDocument {
+            version: JSON_SCHEMA_VERSION,
+            header: self.header.clone(),
+            tempo: self.tempo.clone(),
+            measures: self.measures.clone(),
+            notes: self
+                .events
+                .iter()
+                .filter(|note| note.event.hold_id().is_none())
+                .copied()
+                .collect(),
+            holds: self.holds()?,
+        };
+        Ok(serde_json::to_string_pretty(&document).unwrap() + "\n")
+    }
+
+    /// Reads a chart written by [`Chart::to_json`], or edited from one.
+    ///
+    /// A hold's notes are taken as they are, so its start, waypoints and
+    /// end have to share one id and pair up the way they would in SSF.
+    pub fn from_json(input: &str) -> Result<Chart, JsonError> {
+        let Version { version } = serde_json::from_str(input).map_err(json_error)?;
+        if version != JSON_SCHEMA_VERSION {
+            return Err(JsonError::UnsupportedVersion(version));
+        }
+        let document: ChartDocument = serde_json::from_str(input).map_err(json_error)?;
+
+        for (measure_num, measure) in document.measures.iter().enumerate() {
+            if measure.resolution == 0 || measure.signature.num == 0 || measure.signature.denomi == 0
+            {
+                return Err(JsonError::InvalidMeasure(measure_num));
+            }
+        }
+        let mut events = document.notes;
+        for hold in document.holds {
+            events.push(TimedEvent {
+                measure: hold.measure,
+                tick: hold.tick,
+                event: hold.start,
+            });
+            events.extend(hold.points);
+        }
+        for note in &events {
+            let resolution = document
+                .measures
+                .get(note.measure)
+                .map_or(TICKS_PER_MEASURE, |measure| measure.resolution);
+            if note.tick >= resolution {
+                return Err(JsonError::TickOutOfRange {
+                    measure: note.measure,
+                    tick: note.tick,
+                });
+            }
+            // The same digits an SSF note code could hold.
+            let fits = |(lane, width): (u8, u8)| lane <= 15 && (1..=16).contains(&width);
+            if !note.event.spans().into_iter().all(fits) {
+                return Err(JsonError::InvalidLane {
+                    measure: note.measure,
+                    tick: note.tick,
+                });
+            }
+        }
+
+        let chart = Chart {
+            header: document.header,
+            tempo: document.tempo,
+            measures: document.measures,
+            events: events.into_iter().collect(),
+        };
+        chart.holds()?;
+        Ok(chart)
+    }
+}
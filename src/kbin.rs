@@ -0,0 +1,402 @@
+use roxmltree::{Document, Node};
+use xml_builder::XMLElement;
+
+use crate::error::KbinError;
+use crate::xml::{escape_text, render};
+
+const SIGNATURE: u8 = 0xA0;
+const SIG_COMPRESSED: u8 = 0x42;
+const SIG_UNCOMPRESSED: u8 = 0x45;
+const ENCODING_ASCII: u8 = 0x20;
+const ENCODING_UTF8: u8 = 0xA0;
+
+/// Set on a node type for arrays, and always on the end markers.
+const ARRAY_FLAG: u8 = 0x40;
+const NODE_ATTR: u8 = 0x2E;
+const NODE_END: u8 = 0xBE;
+const END_SECTION: u8 = 0xBF;
+
+/// Characters a compressed node name can use, each packed into 6 bits.
+const SIXBIT_CHARS: &[u8; 64] = b"0123456789:ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz";
+
+/// The node types the converter writes, as `(id, __type name)`.
+const NODE_TYPES: [(u8, &str); 11] = [
+    (1, "void"),
+    (2, "s8"),
+    (3, "u8"),
+    (4, "s16"),
+    (5, "u16"),
+    (6, "s32"),
+    (7, "u32"),
+    (8, "s64"),
+    (9, "u64"),
+    (11, "str"),
+    (52, "bool"),
+];
+
+fn type_id(name: &str) -> Option<u8> {
+    NODE_TYPES.iter().find(|(_, n)| *n == name).map(|(id, _)| *id)
+}
+
+fn type_name(id: u8) -> Option<&'static str> {
+    NODE_TYPES.iter().find(|(i, _)| *i == id).map(|(_, name)| *name)
+}
+
+/// Encodes the text of a numeric node as big-endian bytes.
+fn encode_number(type_name: &str, text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    Some(match type_name {
+        "s8" => text.parse::<i8>().ok()?.to_be_bytes().to_vec(),
+        "u8" => text.parse::<u8>().ok()?.to_be_bytes().to_vec(),
+        "bool" => vec![text.parse::<u8>().ok().filter(|value| *value <= 1)?],
+        "s16" => text.parse::<i16>().ok()?.to_be_bytes().to_vec(),
+        "u16" => text.parse::<u16>().ok()?.to_be_bytes().to_vec(),
+        "s32" => text.parse::<i32>().ok()?.to_be_bytes().to_vec(),
+        "u32" => text.parse::<u32>().ok()?.to_be_bytes().to_vec(),
+        "s64" => text.parse::<i64>().ok()?.to_be_bytes().to_vec(),
+        "u64" => text.parse::<u64>().ok()?.to_be_bytes().to_vec(),
+        _ => return None,
+    })
+}
+
+fn number_size(type_name: &str) -> usize {
+    match type_name {
+        "s8" | "u8" | "bool" => 1,
+        "s16" | "u16" => 2,
+        "s32" | "u32" => 4,
+        _ => 8,
+    }
+}
+
+fn decode_number(type_name: &str, bytes: &[u8]) -> String {
+    match type_name {
+        "s8" => (bytes[0] as i8).to_string(),
+        "u8" | "bool" => bytes[0].to_string(),
+        "s16" => i16::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        "u16" => u16::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        "s32" => i32::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        "u32" => u32::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        "s64" => i64::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        _ => u64::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+    }
+}
+
+fn align(buf: &mut Vec<u8>) {
+    while !buf.len().is_multiple_of(4) {
+        buf.push(0);
+    }
+}
+
+fn align_offset(offset: usize) -> usize {
+    offset.div_ceil(4) * 4
+}
+
+/// The data section. One- and two-byte values share padded 4-byte slots,
+/// so later small values fill the gaps left by earlier ones.
+struct DataWriter {
+    buf: Vec<u8>,
+    byte_offset: usize,
+    word_offset: usize,
+}
+
+impl DataWriter {
+    fn append_aligned(&mut self, bytes: &[u8]) {
+        if self.byte_offset.is_multiple_of(4) {
+            self.byte_offset = self.buf.len();
+        }
+        if self.word_offset.is_multiple_of(4) {
+            self.word_offset = self.buf.len();
+        }
+        let offset = match bytes.len() {
+            1 => &mut self.byte_offset,
+            2 => &mut self.word_offset,
+            _ => {
+                self.buf.extend_from_slice(bytes);
+                align(&mut self.buf);
+                return;
+            }
+        };
+        if offset.is_multiple_of(4) {
+            self.buf.extend_from_slice(&[0; 4]);
+        }
+        self.buf[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+        *offset += bytes.len();
+    }
+
+    fn append_sized(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.buf.extend_from_slice(bytes);
+        align(&mut self.buf);
+    }
+
+    fn append_string(&mut self, value: &str) {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        self.append_sized(&bytes);
+    }
+}
+
+fn append_name(nodes: &mut Vec<u8>, name: &str) -> Result<(), KbinError> {
+    let invalid = || KbinError::InvalidName(name.to_string());
+    if name.is_empty() || name.len() > u8::MAX as usize {
+        return Err(invalid());
+    }
+    nodes.push(name.len() as u8);
+    let mut bits = 0_u32;
+    let mut bit_count = 0;
+    for c in name.bytes() {
+        let index = SIXBIT_CHARS.iter().position(|&s| s == c).ok_or_else(invalid)?;
+        bits = (bits << 6) | index as u32;
+        bit_count += 6;
+        while bit_count >= 8 {
+            bit_count -= 8;
+            nodes.push((bits >> bit_count) as u8);
+        }
+    }
+    if bit_count > 0 {
+        nodes.push((bits << (8 - bit_count)) as u8);
+    }
+    Ok(())
+}
+
+fn encode_node(node: Node, nodes: &mut Vec<u8>, data: &mut DataWriter) -> Result<(), KbinError> {
+    let name = node.tag_name().name();
+    let type_name = node.attribute("__type").unwrap_or("void");
+    let id = type_id(type_name).ok_or_else(|| KbinError::UnsupportedType(type_name.to_string()))?;
+    if node.attribute("__count").is_some() {
+        return Err(KbinError::UnsupportedType(format!("{} array", type_name)));
+    }
+
+    nodes.push(id);
+    append_name(nodes, name)?;
+
+    let text = node.text().unwrap_or("");
+    match type_name {
+        "void" => {}
+        "str" => data.append_string(text),
+        _ => {
+            let bytes = encode_number(type_name, text).ok_or_else(|| KbinError::InvalidValue {
+                name: name.to_string(),
+                value: text.to_string(),
+            })?;
+            data.append_aligned(&bytes);
+        }
+    }
+
+    let mut attributes: Vec<_> = node
+        .attributes()
+        .filter(|attribute| !attribute.name().starts_with("__"))
+        .collect();
+    attributes.sort_by_key(|attribute| attribute.name());
+    for attribute in attributes {
+        data.append_string(attribute.value());
+        nodes.push(NODE_ATTR);
+        append_name(nodes, attribute.name())?;
+    }
+
+    for child in node.children().filter(|child| child.is_element()) {
+        encode_node(child, nodes, data)?;
+    }
+    nodes.push(NODE_END | ARRAY_FLAG);
+    Ok(())
+}
+
+/// Encodes a text XML document as Konami binary XML, with compressed node
+/// names and UTF-8 strings.
+///
+/// Each element's `__type` attribute picks its node type, and elements
+/// without one become `void` nodes.
+pub fn xml_to_kbin(xml: &str) -> Result<Vec<u8>, KbinError> {
+    let document = Document::parse(xml).map_err(|e| KbinError::Xml(e.to_string()))?;
+    let mut nodes = Vec::new();
+    let mut data = DataWriter {
+        buf: Vec::new(),
+        byte_offset: 0,
+        word_offset: 0,
+    };
+    encode_node(document.root_element(), &mut nodes, &mut data)?;
+    nodes.push(END_SECTION | ARRAY_FLAG);
+    align(&mut nodes);
+
+    let mut kbin = vec![
+        SIGNATURE,
+        SIG_COMPRESSED,
+        ENCODING_UTF8,
+        0xFF ^ ENCODING_UTF8,
+    ];
+    kbin.extend_from_slice(&(nodes.len() as u32).to_be_bytes());
+    kbin.extend_from_slice(&nodes);
+    kbin.extend_from_slice(&(data.buf.len() as u32).to_be_bytes());
+    kbin.extend_from_slice(&data.buf);
+    Ok(kbin)
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], KbinError> {
+        let bytes = self
+            .buf
+            .get(self.offset..self.offset + count)
+            .ok_or(KbinError::Truncated)?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, KbinError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, KbinError> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+}
+
+/// Reads the data section back in the order [`DataWriter`] filled it.
+struct DataReader<'a> {
+    reader: Reader<'a>,
+    byte_offset: usize,
+    word_offset: usize,
+}
+
+impl<'a> DataReader<'a> {
+    fn grab_aligned(&mut self, size: usize) -> Result<&'a [u8], KbinError> {
+        if self.byte_offset.is_multiple_of(4) {
+            self.byte_offset = self.reader.offset;
+        }
+        if self.word_offset.is_multiple_of(4) {
+            self.word_offset = self.reader.offset;
+        }
+        let offset = match size {
+            1 => &mut self.byte_offset,
+            2 => &mut self.word_offset,
+            _ => {
+                let bytes = self.reader.bytes(size)?;
+                self.reader.offset = align_offset(self.reader.offset);
+                return Ok(bytes);
+            }
+        };
+        let bytes = self
+            .reader
+            .buf
+            .get(*offset..*offset + size)
+            .ok_or(KbinError::Truncated)?;
+        *offset += size;
+        let trailing = self.byte_offset.max(self.word_offset);
+        if self.reader.offset < trailing {
+            self.reader.offset = align_offset(trailing);
+        }
+        Ok(bytes)
+    }
+
+    fn grab_string(&mut self) -> Result<String, KbinError> {
+        let length = self.reader.u32()? as usize;
+        let bytes = self.reader.bytes(length)?;
+        self.reader.offset = align_offset(self.reader.offset);
+        let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+        String::from_utf8(bytes.to_vec()).map_err(|_| KbinError::InvalidString)
+    }
+}
+
+fn read_name(nodes: &mut Reader, compressed: bool) -> Result<String, KbinError> {
+    if !compressed {
+        let length = (nodes.u8()? & !ARRAY_FLAG) as usize + 1;
+        return String::from_utf8(nodes.bytes(length)?.to_vec()).map_err(|_| KbinError::InvalidString);
+    }
+    let length = nodes.u8()? as usize;
+    let packed = nodes.bytes((length * 6).div_ceil(8))?;
+    let mut name = String::new();
+    let (mut bits, mut bit_count) = (0_u32, 0);
+    let mut packed = packed.iter();
+    while name.len() < length {
+        if bit_count < 6 {
+            bits = (bits << 8) | *packed.next().unwrap() as u32;
+            bit_count += 8;
+        }
+        bit_count -= 6;
+        name.push(SIXBIT_CHARS[((bits >> bit_count) & 0x3F) as usize] as char);
+    }
+    Ok(name)
+}
+
+/// Decodes Konami binary XML back into the text XML [`xml_to_kbin`] reads,
+/// with a `__type` attribute on every typed element.
+pub fn kbin_to_xml(kbin: &[u8]) -> Result<String, KbinError> {
+    let mut header = Reader { buf: kbin, offset: 0 };
+    let [signature, compression, encoding, check] = header.bytes(4)?.try_into().unwrap();
+    let compressed = match compression {
+        SIG_COMPRESSED => true,
+        SIG_UNCOMPRESSED => false,
+        _ => return Err(KbinError::NotKbin),
+    };
+    if signature != SIGNATURE || encoding ^ check != 0xFF {
+        return Err(KbinError::NotKbin);
+    }
+    if encoding != ENCODING_UTF8 && encoding != ENCODING_ASCII {
+        return Err(KbinError::UnsupportedEncoding(encoding));
+    }
+
+    let node_length = header.u32()? as usize;
+    let mut nodes = Reader {
+        buf: header.bytes(node_length)?,
+        offset: 0,
+    };
+    let data_length = header.u32()? as usize;
+    let mut data = DataReader {
+        reader: Reader {
+            buf: header.bytes(data_length)?,
+            offset: 0,
+        },
+        byte_offset: 0,
+        word_offset: 0,
+    };
+
+    let mut stack: Vec<XMLElement> = Vec::new();
+    let mut root = None;
+    loop {
+        let node_type = nodes.u8()?;
+        match node_type & !ARRAY_FLAG {
+            END_SECTION => break,
+            NODE_END => {
+                let element = stack.pop().ok_or(KbinError::Unbalanced)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.add_child(element).unwrap(),
+                    None => root = Some(element),
+                }
+            }
+            NODE_ATTR => {
+                let name = read_name(&mut nodes, compressed)?;
+                let value = data.grab_string()?;
+                let element = stack.last_mut().ok_or(KbinError::Unbalanced)?;
+                element.add_attribute(&name, &value);
+            }
+            _ if node_type & ARRAY_FLAG != 0 => {
+                let name = type_name(node_type & !ARRAY_FLAG).unwrap_or("unknown");
+                return Err(KbinError::UnsupportedType(format!("{} array", name)));
+            }
+            id => {
+                let type_name = type_name(id).ok_or(KbinError::UnknownNodeType(id))?;
+                let name = read_name(&mut nodes, compressed)?;
+                let mut element = XMLElement::new(&name);
+                match type_name {
+                    "void" => {}
+                    "str" => {
+                        element.add_attribute("__type", type_name);
+                        element.add_text(escape_text(&data.grab_string()?)).unwrap();
+                    }
+                    _ => {
+                        element.add_attribute("__type", type_name);
+                        let bytes = data.grab_aligned(number_size(type_name))?;
+                        element.add_text(decode_number(type_name, bytes)).unwrap();
+                    }
+                }
+                stack.push(element);
+            }
+        }
+    }
+
+    root.map(render).ok_or(KbinError::Unbalanced)
+}
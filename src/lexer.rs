@@ -0,0 +1,121 @@
+use crate::error::{ParseError, ParseErrorKind};
+
+/// What one line of an SSF file is, decided by its shape alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Token<'a> {
+    /// `#NAME argument`, or `#mmmcc: data` with or without a space after
+    /// the colon. The argument is empty when there is none.
+    Command { name: &'a str, argument: &'a str },
+    /// `#START`, which ends the header.
+    Start,
+    /// A measure number, optionally followed by its resolution.
+    Measure {
+        number: &'a str,
+        resolution: Option<&'a str>,
+    },
+    /// `tick:notes`, with the notes split on commas.
+    Tick { tick: &'a str, notes: &'a str },
+    /// `END`, which ends the notes.
+    End,
+}
+
+/// A line that holds a token. Every `&str` in it is a slice of `text`, so
+/// columns can be worked out from it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Line<'a> {
+    /// 1-based line number.
+    pub number: usize,
+    pub text: &'a str,
+    pub token: Token<'a>,
+}
+
+/// `input` without its UTF-8 byte order mark, if it has one.
+pub(crate) fn strip_bom(input: &str) -> &str {
+    input.strip_prefix('\u{feff}').unwrap_or(input)
+}
+
+/// 1-based column of `part` within `line`, where `part` is a subslice of it.
+pub(crate) fn column_of(line: &str, part: &str) -> usize {
+    let offset = part.as_ptr() as usize - line.as_ptr() as usize;
+    line[..offset].chars().count() + 1
+}
+
+/// Splits `NAME argument`, the part of a command after its `#`. A name
+/// ends at the first space, or just after the first colon for channel and
+/// BPM definitions. The argument is trimmed, and empty when there is none.
+pub(crate) fn split_command(rest: &str) -> (&str, &str) {
+    let name_end = match rest.find(|c: char| c.is_whitespace() || c == ':') {
+        Some(index) if rest[index..].starts_with(':') => index + 1,
+        Some(index) => index,
+        None => rest.len(),
+    };
+    let (name, argument) = rest.split_at(name_end);
+    (name, argument.trim())
+}
+
+fn command(rest: &str) -> Token<'_> {
+    let (name, argument) = split_command(rest);
+    if argument.is_empty() && name.eq_ignore_ascii_case("START") {
+        return Token::Start;
+    }
+    Token::Command { name, argument }
+}
+
+/// Reads one line, or `None` for blank lines and comments.
+fn token(text: &str) -> Option<Result<Token<'_>, ParseErrorKind>> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() || trimmed.starts_with("//") {
+        return None;
+    }
+    if let Some(rest) = trimmed.strip_prefix('#') {
+        // Header arguments such as titles are kept whole, `//` and all.
+        return Some(Ok(command(rest)));
+    }
+
+    let data = match trimmed.find("//") {
+        Some(comment) => trimmed[..comment].trim_end(),
+        None => trimmed,
+    };
+    if data.eq_ignore_ascii_case("END") {
+        return Some(Ok(Token::End));
+    }
+    if let Some((tick, notes)) = data.split_once(':') {
+        return Some(Ok(Token::Tick {
+            tick: tick.trim_end(),
+            notes: notes.trim_start(),
+        }));
+    }
+    if data.starts_with(|c: char| c.is_ascii_digit()) {
+        let mut parts = data.split_whitespace();
+        let number = parts.next().unwrap();
+        let resolution = parts.next();
+        if parts.next().is_none() {
+            return Some(Ok(Token::Measure { number, resolution }));
+        }
+    }
+    Some(Err(ParseErrorKind::UnrecognizedLine))
+}
+
+/// Splits SSF source into its meaningful lines, skipping a leading byte
+/// order mark, blank lines and `//` comments. Line endings may be `\n` or
+/// `\r\n`, and whitespace around a line is ignored.
+///
+/// Data lines may end in a `//` comment too; header lines may not, since
+/// titles and file names can contain `//`.
+pub(crate) fn lines(input: &str) -> impl Iterator<Item = Result<Line<'_>, ParseError>> {
+    let input = strip_bom(input);
+    input.lines().enumerate().filter_map(|(index, text)| {
+        let number = index + 1;
+        Some(match token(text)? {
+            Ok(token) => Ok(Line {
+                number,
+                text,
+                token,
+            }),
+            Err(kind) => {
+                let trimmed = text.trim();
+                Err(ParseError::new(number, column_of(text, trimmed), trimmed, kind))
+            }
+        })
+    })
+}
@@ -0,0 +1,54 @@
+//! Converts SSF charts into DRS sequence XML.
+
+mod chart;
+mod drs;
+mod drs_reader;
+mod error;
+mod hold;
+#[cfg(feature = "serde")]
+mod json;
+mod kbin;
+mod lexer;
+mod lint;
+mod metadata;
+mod note;
+mod rational;
+mod render;
+mod ssf;
+mod stats;
+mod sus;
+mod tempo;
+mod timing;
+mod transform;
+mod view;
+mod xml;
+
+pub use chart::{
+    Chart, Difficulty, EventList, Header, Measure, TimedEvent, TimeSignature, LANES,
+    TICKS_PER_MEASURE,
+};
+pub use drs_reader::parse_drs_xml;
+pub use error::{
+    DrsReadError, DrsWriteError, HoldError, KbinError, MetadataError, ParseError, ParseErrorKind,
+    TransformError, WriteError,
+};
+#[cfg(feature = "serde")]
+pub use error::JsonError;
+#[cfg(feature = "png")]
+pub use error::RasterError;
+pub use hold::Hold;
+#[cfg(feature = "serde")]
+pub use json::JSON_SCHEMA_VERSION;
+pub use kbin::{kbin_to_xml, xml_to_kbin};
+pub use lint::{diagnostics_json, lint, Diagnostic, Rule, Severity};
+pub use metadata::{bpm_range, check_song, check_song_id, music_list_xml, sequence_name};
+pub use note::NoteEvent;
+pub use rational::{ParseRationalError, Rational};
+#[cfg(feature = "png")]
+pub use render::svg_to_png;
+pub use ssf::parse_ssf;
+pub use stats::{stats, Stats, NPS_WINDOW_MS};
+pub use sus::{parse_sus, SusWarning};
+pub use tempo::{Bpm, TempoChange, TempoMap};
+pub use timing::Timeline;
+pub use view::{GridStyle, GridView};
@@ -0,0 +1,285 @@
+use std::fmt;
+
+use crate::chart::{Chart, LANES};
+use crate::hold::{hold_id, Hold};
+use crate::note::NoteEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A check run by [`lint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rule {
+    /// A note whose lanes run off the pad or that has no width.
+    LaneOutOfRange,
+    /// Two notes for the same foot on the same tick covering the same lane.
+    OverlappingNotes,
+    /// A note for a foot that is still holding.
+    StepInHold,
+    /// A hold that ends on the tick it starts.
+    ZeroLengthHold,
+    /// A hold waypoint or end on the same tick as the point before it.
+    /// [`Chart::holds`] already puts each hold's points in chart order, so
+    /// this is the only way they can be out of order.
+    WaypointOrder,
+    /// More than one jump or down on the same tick.
+    StackedJumpDown,
+    /// A hold start, waypoint or end that doesn't pair up.
+    UnpairedHold,
+}
+
+impl Rule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::LaneOutOfRange => "lane-out-of-range",
+            Rule::OverlappingNotes => "overlapping-notes",
+            Rule::StepInHold => "step-in-hold",
+            Rule::ZeroLengthHold => "zero-length-hold",
+            Rule::WaypointOrder => "waypoint-order",
+            Rule::StackedJumpDown => "stacked-jump-down",
+            Rule::UnpairedHold => "unpaired-hold",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Rule::ZeroLengthHold => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// One problem found by [`lint`], at the measure/tick it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub measure: usize,
+    pub tick: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.rule.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}] measure {}, tick {}: {}",
+            self.severity(),
+            self.rule.name(),
+            self.measure,
+            self.tick,
+            self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Foot {
+    Left,
+    Right,
+}
+
+fn foot(event: &NoteEvent) -> Option<Foot> {
+    match event {
+        NoteEvent::LeftStep { .. } | NoteEvent::LeftHoldStart { .. } => Some(Foot::Left),
+        NoteEvent::RightStep { .. } | NoteEvent::RightHoldStart { .. } => Some(Foot::Right),
+        _ => None,
+    }
+}
+
+fn lane_range(lane: u8, width: u8) -> String {
+    format!("lanes {}-{}", lane, lane as u32 + width as u32 - 1)
+}
+
+fn check_notes(chart: &Chart, diagnostics: &mut Vec<Diagnostic>) {
+    for tick in chart.events.ticks() {
+        let (measure_num, tick_num) = tick[0].position();
+        let mut report = |rule, message| {
+            diagnostics.push(Diagnostic {
+                rule,
+                measure: measure_num,
+                tick: tick_num,
+                message,
+            })
+        };
+
+        for event in tick.iter().map(|note| &note.event) {
+            for (lane, width) in event.spans() {
+                if width == 0 {
+                    report(Rule::LaneOutOfRange, format!("{:?} has no width", event));
+                } else if lane as usize + width as usize > LANES {
+                    report(
+                        Rule::LaneOutOfRange,
+                        format!(
+                            "{:?} covers {}, past the {} lanes of the pad",
+                            event,
+                            lane_range(lane, width),
+                            LANES
+                        ),
+                    );
+                }
+            }
+        }
+
+        for (index, note) in tick.iter().enumerate() {
+            let event = &note.event;
+            let Some(event_foot) = foot(event) else {
+                continue;
+            };
+            for other in tick[index + 1..].iter().map(|note| &note.event) {
+                if foot(other) != Some(event_foot) {
+                    continue;
+                }
+                let [(a_lane, a_width), (b_lane, b_width)] = [event.spans()[0], other.spans()[0]]
+                    .map(|(lane, width)| (lane as u32, width as u32));
+                if a_lane < b_lane + b_width && b_lane < a_lane + a_width {
+                    report(
+                        Rule::OverlappingNotes,
+                        format!("{:?} overlaps {:?}", event, other),
+                    );
+                }
+            }
+        }
+
+        let stacked = tick
+            .iter()
+            .filter(|note| matches!(note.event, NoteEvent::Jump | NoteEvent::Down))
+            .count();
+        if stacked > 1 {
+            report(
+                Rule::StackedJumpDown,
+                format!("{} jumps and downs on one tick", stacked),
+            );
+        }
+    }
+}
+
+fn check_holds(chart: &Chart, holds: &[Hold], diagnostics: &mut Vec<Diagnostic>) {
+    for hold in holds {
+        let start = (hold.measure, hold.tick);
+        let end = hold.end();
+        if end.position() == start {
+            diagnostics.push(Diagnostic {
+                rule: Rule::ZeroLengthHold,
+                measure: hold.measure,
+                tick: hold.tick,
+                message: format!("hold {} ends on the tick it starts", hold_id(hold.id)),
+            });
+        } else {
+            let mut previous = start;
+            for point in &hold.points {
+                let position = point.position();
+                if position == previous {
+                    diagnostics.push(Diagnostic {
+                        rule: Rule::WaypointOrder,
+                        measure: point.measure,
+                        tick: point.tick,
+                        message: format!(
+                            "{:?} is on the same tick as the previous point of hold {}",
+                            point.event,
+                            hold_id(hold.id)
+                        ),
+                    });
+                }
+                previous = position;
+            }
+        }
+
+        // Any note for the holding foot strictly between the hold's start
+        // and end.
+        let hold_foot = foot(&hold.start);
+        for note in chart.events.between(start, end.position()) {
+            if foot(&note.event) != hold_foot {
+                continue;
+            }
+            diagnostics.push(Diagnostic {
+                rule: Rule::StepInHold,
+                measure: note.measure,
+                tick: note.tick,
+                message: format!(
+                    "{:?} while hold {} from measure {}, tick {} is held",
+                    note.event,
+                    hold_id(hold.id),
+                    hold.measure,
+                    hold.tick
+                ),
+            });
+        }
+    }
+}
+
+/// Checks a parsed chart for layouts the game can't play, returning every
+/// problem found in chart order.
+pub fn lint(chart: &Chart) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check_notes(chart, &mut diagnostics);
+    match chart.holds() {
+        Ok(holds) => check_holds(chart, &holds, &mut diagnostics),
+        Err(error) => {
+            let (measure, tick) = error.position();
+            diagnostics.push(Diagnostic {
+                rule: Rule::UnpairedHold,
+                measure,
+                tick,
+                message: error.reason(),
+            });
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.measure, diagnostic.tick, diagnostic.rule));
+    diagnostics
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Renders lint results as a JSON array of objects with `rule`,
+/// `severity`, `measure`, `tick` and `message` fields.
+pub fn diagnostics_json(diagnostics: &[Diagnostic]) -> String {
+    let entries: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            format!(
+                "  {{\"rule\": {}, \"severity\": {}, \"measure\": {}, \"tick\": {}, \"message\": {}}}",
+                json_string(diagnostic.rule.name()),
+                json_string(&diagnostic.severity().to_string()),
+                diagnostic.measure,
+                diagnostic.tick,
+                json_string(&diagnostic.message)
+            )
+        })
+        .collect();
+    if entries.is_empty() {
+        "[]\n".to_string()
+    } else {
+        format!("[\n{}\n]\n", entries.join(",\n"))
+    }
+}
@@ -1,656 +1,945 @@
-use xml_builder::{XMLBuilder, XMLElement, XMLVersion};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::{IsTerminal, Read, Write};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use drs_converter::{
+    bpm_range, check_song, check_song_id, diagnostics_json, kbin_to_xml, lint, music_list_xml,
+    parse_drs_xml, parse_ssf, parse_sus, sequence_name, stats, xml_to_kbin, Chart, GridStyle,
+    GridView, KbinError, Rational, Severity, TransformError,
+};
+#[cfg(feature = "png")]
+use drs_converter::svg_to_png;
+
+#[derive(Parser)]
+#[command(version, about = "Converts SSF charts into DRS sequence XML")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also dump every parsed tick
+    #[arg(short, long, global = true)]
+    verbose: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert an SSF chart into DRS sequence XML
+    Convert {
+        #[command(flatten)]
+        input: Input,
+
+        /// Output file, or `-` for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
+
+        /// Extra milliseconds added to the chart's WAVEOFFSET
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        offset: i32,
+
+        /// Output encoding
+        #[arg(long, value_enum, default_value = "xml")]
+        format: Format,
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum NoteEvent {
-    LeftStep {
-        lane: u8,
-        width: u8,
+        #[command(flatten)]
+        transforms: Transforms,
+    },
+    /// Print the header fields of an SSF chart
+    Info {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Check that an SSF chart parses and its holds pair up
+    Validate {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Check an SSF chart for layouts the game can't play
+    Lint {
+        #[command(flatten)]
+        input: Input,
+
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print note counts and density for an SSF chart
+    Stats {
+        #[command(flatten)]
+        input: Input,
+
+        /// Print the stats as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Draw an SSF chart as preview images, a few measures to a page
+    Render {
+        #[command(flatten)]
+        input: Input,
+
+        /// Output file, or `-` for stdout. With more than one page, each
+        /// page is written next to it with its number added, as
+        /// `preview-1.svg`, `preview-2.svg` and so on
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
+
+        /// Measures on each page
+        #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+        measures: u32,
+
+        /// Image format
+        #[arg(long, value_enum, default_value = "svg")]
+        format: ImageFormat,
     },
-    RightStep {
-        lane: u8,
-        width: u8,
+    /// Print an SSF chart as a lane grid in the terminal
+    View {
+        #[command(flatten)]
+        input: Input,
+
+        /// Measure to start at
+        #[arg(long, default_value_t = 0)]
+        measure: usize,
+
+        /// Measures on each page. In a terminal, pages are shown one at a
+        /// time; otherwise only this many measures are printed, or the rest
+        /// of the chart if it isn't given
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        measures: Option<u32>,
+
+        /// Draw with ASCII instead of box-drawing characters
+        #[arg(long)]
+        ascii: bool,
     },
-    Jump,
-    Down,
-    LeftHoldStart {
-        id: u8,
-        lane: u8,
-        width: u8,
+    /// Rewrite an SSF chart in canonical form
+    Fmt {
+        #[command(flatten)]
+        input: Input,
+
+        /// Output file, or `-` for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
     },
-    RightHoldStart {
-        id: u8,
-        lane: u8,
-        width: u8,
+    /// Write an SSF chart as JSON, for editing with scripts
+    #[cfg(feature = "serde")]
+    ExportJson {
+        #[command(flatten)]
+        input: Input,
+
+        /// Output file, or `-` for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
     },
-    SlideWaypoint {
-        id: u8,
-        lane: u8,
-        width: u8,
+    /// Convert a chart written by `export-json` back into an SSF chart
+    #[cfg(feature = "serde")]
+    ImportJson {
+        /// Input JSON chart, or `-` for stdin
+        #[arg(default_value = "-")]
+        input: PathBuf,
+
+        /// Output file, or `-` for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
     },
-    SlideEnd {
-        id: u8,
-        lane: u8,
-        width: u8,
+    /// Convert a DRS sequence XML, text or binary, back into an SSF chart
+    ImportDrs {
+        /// Input DRS sequence XML, or `-` for stdin
+        #[arg(default_value = "-")]
+        input: PathBuf,
+
+        /// Output file, or `-` for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
     },
-    SimpleSkidWaypoint {
-        id: u8,
-        lane: u8,
-        width: u8,
+    /// Convert a SUS chart into an SSF chart
+    ImportSus {
+        /// Input SUS chart, or `-` for stdin
+        #[arg(default_value = "-")]
+        input: PathBuf,
+
+        /// Output file, or `-` for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
     },
-    ComplexSkidWaypoint {
-        id: u8,
-        lane_start: u8,
-        width_start: u8,
-        lane_end: u8,
-        width_end: u8,
+    /// Convert every SSF chart under a directory into a mirrored output tree
+    Batch {
+        /// Directory to search for `.ssf` files
+        input: PathBuf,
+
+        /// Directory to write the converted charts to
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Output encoding
+        #[arg(long, value_enum, default_value = "xml")]
+        format: Format,
+
+        /// Charts to convert at once, defaulting to one per CPU
+        #[arg(short, long)]
+        jobs: Option<usize>,
     },
-    SimpleSkidEnd {
-        id: u8,
-        lane: u8,
-        width: u8,
+    /// Lay out one song's folder with every difficulty and its audio and jacket
+    Package {
+        /// SSF files, or directories to search for them
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Directory to create the song folder in
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// SONGID to package, needed when the inputs hold more than one song
+        #[arg(long)]
+        song_id: Option<String>,
+
+        /// Output encoding
+        #[arg(long, value_enum, default_value = "xml")]
+        format: Format,
     },
-    ComplexSkidEnd {
-        id: u8,
-        lane_start: u8,
-        width_start: u8,
-        lane_end: u8,
-        width_end: u8,
+    /// Build the music list XML for a set of charts, one entry per SONGID
+    MusicList {
+        /// Input SSF files, every difficulty of every song to list
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output file, or `-` for stdout
+        #[arg(short, long, default_value = "-")]
+        output: PathBuf,
+
+        /// Output encoding
+        #[arg(long, value_enum, default_value = "xml")]
+        format: Format,
     },
 }
 
-impl NoteEvent {
-    fn from_string(input_string: String) -> NoteEvent {
-        let chars: Vec<char> = input_string.chars().collect();
-        match chars[0] {
-            '0' => NoteEvent::LeftStep {
-                lane: u8::from_str_radix(&chars[1].to_string(), 16).unwrap(),
-                width: u8::from_str_radix(&chars[2].to_string(), 16).unwrap() + 1,
-            },
-            '1' => NoteEvent::RightStep {
-                lane: u8::from_str_radix(&chars[1].to_string(), 16).unwrap(),
-                width: u8::from_str_radix(&chars[2].to_string(), 16).unwrap() + 1,
-            },
-            '2' => NoteEvent::Jump,
-            '3' => NoteEvent::Down,
-            '4' => NoteEvent::LeftHoldStart {
-                id: u8::from_str_radix(&chars[1].to_string(), 36).unwrap(),
-                lane: u8::from_str_radix(&chars[2].to_string(), 16).unwrap(),
-                width: u8::from_str_radix(&chars[3].to_string(), 16).unwrap() + 1,
-            },
-            '5' => NoteEvent::RightHoldStart {
-                id: u8::from_str_radix(&chars[1].to_string(), 36).unwrap(),
-                lane: u8::from_str_radix(&chars[2].to_string(), 16).unwrap(),
-                width: u8::from_str_radix(&chars[3].to_string(), 16).unwrap() + 1,
-            },
-            '6' => NoteEvent::SlideWaypoint {
-                id: u8::from_str_radix(&chars[1].to_string(), 36).unwrap(),
-                lane: u8::from_str_radix(&chars[2].to_string(), 16).unwrap(),
-                width: u8::from_str_radix(&chars[3].to_string(), 16).unwrap() + 1,
-            },
-            '7' => NoteEvent::SlideEnd {
-                id: u8::from_str_radix(&chars[1].to_string(), 36).unwrap(),
-                lane: u8::from_str_radix(&chars[2].to_string(), 16).unwrap(),
-                width: u8::from_str_radix(&chars[3].to_string(), 16).unwrap() + 1,
-            },
-            '8' => NoteEvent::SimpleSkidWaypoint {
-                id: u8::from_str_radix(&chars[1].to_string(), 36).unwrap(),
-                lane: u8::from_str_radix(&chars[2].to_string(), 16).unwrap(),
-                width: u8::from_str_radix(&chars[3].to_string(), 16).unwrap() + 1,
-            },
-            '9' => NoteEvent::ComplexSkidWaypoint {
-                id: u8::from_str_radix(&chars[1].to_string(), 36).unwrap(),
-                lane_start: u8::from_str_radix(&chars[2].to_string(), 16).unwrap(),
-                width_start: u8::from_str_radix(&chars[3].to_string(), 16).unwrap() + 1,
-                lane_end: u8::from_str_radix(&chars[4].to_string(), 16).unwrap(),
-                width_end: u8::from_str_radix(&chars[5].to_string(), 16).unwrap() + 1,
-            },
-            'A' => NoteEvent::SimpleSkidEnd {
-                id: u8::from_str_radix(&chars[1].to_string(), 36).unwrap(),
-                lane: u8::from_str_radix(&chars[2].to_string(), 16).unwrap(),
-                width: u8::from_str_radix(&chars[3].to_string(), 16).unwrap() + 1,
-            },
-            'B' => NoteEvent::ComplexSkidEnd {
-                id: u8::from_str_radix(&chars[1].to_string(), 36).unwrap(),
-                lane_start: u8::from_str_radix(&chars[2].to_string(), 16).unwrap(),
-                width_start: u8::from_str_radix(&chars[3].to_string(), 16).unwrap() + 1,
-                lane_end: u8::from_str_radix(&chars[4].to_string(), 16).unwrap(),
-                width_end: u8::from_str_radix(&chars[5].to_string(), 16).unwrap() + 1,
-            },
-            _ => panic!(),
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Text XML
+    Xml,
+    /// Konami binary XML, as the game loads it
+    Kbin,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ImageFormat {
+    Svg,
+    /// Rasterised SVG, without measure numbers
+    #[cfg(feature = "png")]
+    Png,
+}
+
+#[derive(Args)]
+struct Input {
+    /// Input SSF file, or `-` for stdin
+    #[arg(default_value = "-")]
+    input: PathBuf,
+}
+
+/// Changes made to a chart before it is converted, applied in the order
+/// listed.
+#[derive(Args)]
+struct Transforms {
+    /// Move every note this many lanes right, or left when negative
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    shift: i32,
+
+    /// Flip the chart left to right, swapping feet
+    #[arg(long)]
+    mirror: bool,
+
+    /// Move notes to random lanes picked from this seed, keeping hold shapes
+    #[arg(long, value_name = "SEED")]
+    random: Option<u64>,
+
+    /// Scale every tempo by this factor, e.g. 0.8 or 6/5, for practice
+    /// versions played against resampled audio
+    #[arg(long, value_name = "FACTOR")]
+    rate: Option<Rational>,
+}
+
+impl Transforms {
+    fn apply(&self, chart: &mut Chart) -> Result<(), TransformError> {
+        if self.shift != 0 {
+            chart.shift_lanes(self.shift)?;
+        }
+        if self.mirror {
+            chart.mirror()?;
         }
+        if let Some(seed) = self.random {
+            chart.randomize_lanes(seed)?;
+        }
+        if let Some(rate) = self.rate {
+            chart.scale_rate(rate)?;
+        }
+        Ok(())
     }
 }
 
-enum LongPoint {
-    Normal {
-        point_time: u32,
-        pos_left: u32,
-        pos_right: u32,
-    },
-    SkidComplex {
-        point_time: u32,
-        pos_left_start: u32,
-        pos_right_start: u32,
-        pos_left_end: u32,
-        pos_right_end: u32,
-    },
-    SkidSimple {
-        point_time: u32,
-        pos_left: u32,
-        pos_right: u32,
-        is_final: bool,
-    },
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
 }
 
-#[derive(Debug)]
-struct Measure {
-    ticks: Vec<Vec<NoteEvent>>,
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
 }
 
-impl Measure {
-    fn new() -> Measure {
-        Measure {
-            ticks: vec![Vec::<NoteEvent>::new(); 192],
-        }
+fn read_input_bytes(path: &Path) -> std::io::Result<Vec<u8>> {
+    if is_stdio(path) {
+        let mut input = Vec::new();
+        std::io::stdin().read_to_end(&mut input)?;
+        Ok(input)
+    } else {
+        std::fs::read(path)
     }
 }
 
-fn measure_tick_to_ms(measure: u32, tick: u32, bpm: u32) -> u32 {
-    let ms_per_beat = 60000_f32 / bpm as f32;
-    let ms_per_measure = ms_per_beat * 4_f32;
-    let ticks_per_measure = 192_f32;
-    let ms_per_tick = ms_per_measure / ticks_per_measure;
-    (measure as f32 * ms_per_measure + tick as f32 * ms_per_tick) as u32
+fn read_input(path: &Path) -> std::io::Result<String> {
+    String::from_utf8(read_input_bytes(path)?)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
 }
 
-fn ms_to_dt(ms: u32, bpm: u32) -> u32 {
-    (ms as f32 * 0.008 * bpm as f32) as u32
+/// Renders text XML in the requested output format.
+fn encode_xml(xml: String, format: Format) -> Result<Vec<u8>, KbinError> {
+    match format {
+        Format::Xml => Ok(xml.into_bytes()),
+        Format::Kbin => xml_to_kbin(&xml),
+    }
 }
 
-fn add_s32_element(xml: &mut XMLElement, name: &str, value: u32) {
-    let mut element = XMLElement::new(name);
-    element.add_attribute("__type", "s32");
-    element.add_text(value.to_string().into()).unwrap();
-    xml.add_child(element).unwrap();
+fn write_output(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if is_stdio(path) {
+        std::io::stdout().write_all(contents)
+    } else {
+        std::fs::write(path, contents)
+    }
 }
 
-fn add_s64_element(xml: &mut XMLElement, name: &str, value: u64) {
-    let mut element = XMLElement::new(name);
-    element.add_attribute("__type", "s64");
-    element.add_text(value.to_string().into()).unwrap();
-    xml.add_child(element).unwrap();
+/// `path` with `-number` added before its extension.
+fn numbered_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, number),
+    };
+    path.with_file_name(name)
 }
 
-fn xml_boilerplate(bpm: u32) -> XMLElement {
-    let mut xml = XMLElement::new("data");
+/// Collects every `.ssf` file under `dir`, in path order.
+fn find_charts(dir: &Path, charts: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            find_charts(&path, charts)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("ssf"))
+        {
+            charts.push(path);
+        }
+    }
+    Ok(())
+}
 
-    add_s32_element(&mut xml, "seq_version", 8);
+/// Runs `f` over `items` on `jobs` threads, keeping results in item order.
+/// An item whose `f` panics gets the panic message as its error, and the
+/// rest carry on.
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<Result<R, String>> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<Result<R, String>>> = items.iter().map(|_| None).collect();
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break;
+                        };
+                        let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(item)))
+                            .map_err(|payload| panic_message(&*payload));
+                        done.push((index, result));
+                    }
+                    done
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (index, result) in worker.join().unwrap() {
+                results[index] = Some(result);
+            }
+        }
+    });
+    results.into_iter().map(Option::unwrap).collect()
+}
 
-    let mut info = XMLElement::new("info");
+/// What a panic said, for reporting it as an error.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error");
+    format!("panicked: {}", message)
+}
 
-    add_s32_element(&mut info, "tick", 480);
+/// Converts one chart of a batch, returning its path under the output tree
+/// and its contents.
+fn convert_for_batch(
+    input: &Path,
+    relative_dir: &Path,
+    format: Format,
+) -> Result<(PathBuf, Vec<u8>), String> {
+    let source = read_input(input).map_err(|e| e.to_string())?;
+    let chart = parse_ssf(&source).map_err(|e| e.to_string())?;
+    let song_id = chart.header.song_id.as_deref().ok_or("missing SONGID")?;
+    check_song_id(song_id).map_err(|e| e.to_string())?;
+    let difficulty = chart.header.difficulty.ok_or("missing DIFFICULTY")?;
+    let xml = chart.to_drs_xml().map_err(|e| e.to_string())?;
+    let contents = encode_xml(xml, format).map_err(|e| e.to_string())?;
+    let name = format!("{}.xml", sequence_name(song_id, difficulty));
+    Ok((relative_dir.join(name), contents))
+}
 
-    let mut bpm_info = XMLElement::new("bpm_info");
-    let mut bpm_ = XMLElement::new("bpm");
-    add_s32_element(&mut bpm_, "time", 0);
-    add_s32_element(&mut bpm_, "delta_time", 0);
-    add_s32_element(&mut bpm_, "bpm", bpm);
-    bpm_info.add_child(bpm_).unwrap();
-    info.add_child(bpm_info).unwrap();
+fn header_fields(chart: &Chart) -> Vec<(&'static str, String)> {
+    let header = &chart.header;
+    let fields = [
+        ("Title", header.title.clone()),
+        ("Artist", header.artist.clone()),
+        ("Designer", header.designer.clone()),
+        ("Difficulty", header.difficulty.map(|d| d.to_string())),
+        ("Level", header.play_level.clone()),
+        ("Song ID", header.song_id.clone()),
+        ("Sound File", header.wave.clone()),
+        ("Sound Offset", header.wave_offset.map(|ms| format!("{} ms", ms))),
+        ("Cover Image", header.jacket.clone()),
+        ("BPM", Some(bpm_range(chart))),
+        ("Padding Bars", header.padding_bars.map(|p| p.to_string())),
+    ];
+    fields
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect()
+}
 
-    let mut measure_info = XMLElement::new("measure_info");
+fn dump_ticks(chart: &Chart) {
+    for tick in chart.events.ticks() {
+        let notes: Vec<_> = tick.iter().map(|note| note.event).collect();
+        eprintln!(
+            "Measure: {:?}, Tick: {:?}, Notes: {:?}",
+            tick[0].measure, tick[0].tick, notes
+        );
+    }
+}
 
-    let mut measure = XMLElement::new("measure");
-    add_s32_element(&mut measure, "time", 0);
-    add_s32_element(&mut measure, "delta_time", 0);
-    add_s32_element(&mut measure, "num", 4);
-    add_s32_element(&mut measure, "denomi", 4);
-    measure_info.add_child(measure).unwrap();
+/// Measures on each page of `view` in a terminal, unless `--measures` says.
+const VIEW_MEASURES: usize = 4;
 
-    info.add_child(measure_info).unwrap();
+/// Shows `view` a page at a time, reading what to show next from stdin.
+fn page_through(view: &GridView, first: usize, per_page: usize) -> std::io::Result<()> {
+    let last_page = view.measure_count().saturating_sub(1) / per_page * per_page;
+    let mut first = first.min(last_page);
+    let mut line = String::new();
+    loop {
+        let end = (first + per_page).min(view.measure_count().max(first + 1));
+        print!("{}", view.page(first..end));
+        eprint!(
+            "measures {}-{} of {}; Enter for next, b for back, a number to jump, q to quit: ",
+            first,
+            end - 1,
+            view.measure_count()
+        );
+        line.clear();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            eprintln!();
+            return Ok(());
+        }
+        first = match line.trim() {
+            "" if first >= last_page => return Ok(()),
+            "" => first + per_page,
+            "b" => first.saturating_sub(per_page),
+            "q" => return Ok(()),
+            measure => match measure.parse::<usize>() {
+                Ok(measure) => measure.min(last_page),
+                Err(_) => {
+                    eprintln!("not a measure: {}", measure);
+                    first
+                }
+            },
+        };
+    }
+}
 
-    xml.add_child(info).unwrap();
+fn load(input: &Input, verbosity: Verbosity) -> Result<Chart, Box<dyn Error>> {
+    load_path(&input.input, verbosity)
+}
 
-    xml
+fn has_extension(path: &Path, wanted: &str) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(wanted))
 }
 
-fn find_end(
-    measure: &Measure,
-    tick_num: usize,
-    id: &u8,
-    waypoints: &mut Vec<LongPoint>,
-    bpm: u32,
-    measure_num: usize,
-    time: u32,
-) -> u32 {
-    for (end_tick_num, end_tick) in measure.ticks.iter().enumerate().skip(tick_num) {
-        for possible_end_event in end_tick {
-            match possible_end_event {
-                NoteEvent::SlideEnd {
-                    id: end_id,
-                    lane: end_lane,
-                    width: end_width,
-                } => {
-                    if end_id == id {
-                        waypoints.push(LongPoint::Normal {
-                            point_time: measure_tick_to_ms(
-                                measure_num as u32,
-                                end_tick_num as u32,
-                                bpm,
-                            ),
-                            pos_left: *end_lane as u32 * 4096,
-                            pos_right: (*end_lane + end_width) as u32 * 4096,
-                        });
-                        return measure_tick_to_ms(measure_num as u32, end_tick_num as u32, bpm);
-                    }
-                }
-                NoteEvent::SlideWaypoint {
-                    id: point_id,
-                    lane,
-                    width,
-                } => {
-                    if id == point_id {
-                        waypoints.push(LongPoint::Normal {
-                            point_time: measure_tick_to_ms(
-                                measure_num as u32,
-                                end_tick_num as u32,
-                                bpm,
-                            ),
-                            pos_left: *lane as u32 * 4096,
-                            pos_right: (*lane + width) as u32 * 4096,
-                        });
-                    }
+/// Reads an SSF chart, or a SUS chart if the file name ends in `.sus`, or
+/// a JSON chart if it ends in `.json` and JSON support is built in.
+fn load_path(path: &Path, verbosity: Verbosity) -> Result<Chart, Box<dyn Error>> {
+    if has_extension(path, "sus") {
+        return load_sus(path, verbosity);
+    }
+    #[cfg(feature = "serde")]
+    if has_extension(path, "json") {
+        return load_json(path);
+    }
+    let source = read_input(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_ssf(&source)
+        .map_err(|e| format!("{}: {}", path.display(), e.snippet(&source)).into())
+}
+
+#[cfg(feature = "serde")]
+fn load_json(path: &Path) -> Result<Chart, Box<dyn Error>> {
+    let source = read_input(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Chart::from_json(&source).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Reads a SUS chart, printing what couldn't be carried over.
+fn load_sus(path: &Path, verbosity: Verbosity) -> Result<Chart, Box<dyn Error>> {
+    let source = read_input(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let (chart, warnings) = parse_sus(&source)
+        .map_err(|e| format!("{}: {}", path.display(), e.snippet(&source)))?;
+    if verbosity >= Verbosity::Normal {
+        for warning in &warnings {
+            eprintln!("{}: warning: {}", path.display(), warning);
+        }
+    }
+    Ok(chart)
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let verbosity = if cli.quiet {
+        Verbosity::Quiet
+    } else if cli.verbose {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    };
+
+    match cli.command {
+        Command::Convert {
+            input,
+            output,
+            offset,
+            format,
+            transforms,
+        } => {
+            let mut chart = load(&input, verbosity)?;
+            transforms
+                .apply(&mut chart)
+                .map_err(|e| format!("{}: {}", input.input.display(), e))?;
+            if let Some(rate) = transforms.rate {
+                if verbosity >= Verbosity::Normal {
+                    eprintln!("audio speed: {} ({:.4}x)", rate, rate.to_f64());
                 }
-                NoteEvent::SimpleSkidEnd {
-                    id: end_id,
-                    lane: end_lane,
-                    width: end_width,
-                } => {
-                    if id == end_id {
-                        waypoints.push(LongPoint::SkidSimple {
-                            point_time: measure_tick_to_ms(
-                                measure_num as u32,
-                                end_tick_num as u32,
-                                bpm,
-                            ),
-                            pos_left: *end_lane as u32 * 4096,
-                            pos_right: (*end_lane + end_width) as u32 * 4096,
-                            is_final: true,
-                        });
-                        return measure_tick_to_ms(measure_num as u32, end_tick_num as u32, bpm);
-                    }
+            }
+            if offset != 0 {
+                let wave_offset = chart.header.wave_offset.unwrap_or(0);
+                let total = wave_offset.checked_add(offset).ok_or_else(|| {
+                    format!(
+                        "--offset {} ms is too large with a WAVEOFFSET of {} ms",
+                        offset, wave_offset
+                    )
+                })?;
+                chart.header.wave_offset = Some(total);
+            }
+            if verbosity >= Verbosity::Normal {
+                for (name, value) in header_fields(&chart) {
+                    eprintln!("{}: {}", name, value);
                 }
-                NoteEvent::SimpleSkidWaypoint {
-                    id: point_id,
-                    lane: point_lane,
-                    width: point_width,
-                } => {
-                    if id == point_id {
-                        waypoints.push(LongPoint::SkidSimple {
-                            point_time: measure_tick_to_ms(
-                                measure_num as u32,
-                                end_tick_num as u32,
-                                bpm,
-                            ),
-                            pos_left: *point_lane as u32 * 4096,
-                            pos_right: (*point_lane + point_width) as u32 * 4096,
-                            is_final: false,
-                        });
-                    }
+            }
+            if verbosity >= Verbosity::Verbose {
+                dump_ticks(&chart);
+            }
+            let xml = chart
+                .to_drs_xml()
+                .map_err(|e| format!("{}: {}", input.input.display(), e))?;
+            write_output(&output, &encode_xml(xml, format)?)?;
+        }
+        Command::Info { input } => {
+            let chart = load(&input, verbosity)?;
+            for (name, value) in header_fields(&chart) {
+                println!("{}: {}", name, value);
+            }
+            if verbosity >= Verbosity::Verbose {
+                dump_ticks(&chart);
+            }
+        }
+        Command::Validate { input } => {
+            let chart = load(&input, verbosity)?;
+            chart
+                .holds()
+                .map_err(|e| format!("{}: {}", input.input.display(), e))?;
+            if verbosity >= Verbosity::Verbose {
+                dump_ticks(&chart);
+            }
+            if verbosity >= Verbosity::Normal {
+                eprintln!("{}: ok", input.input.display());
+            }
+        }
+        Command::Lint { input, json } => {
+            let chart = load(&input, verbosity)?;
+            let diagnostics = lint(&chart);
+            if json {
+                print!("{}", diagnostics_json(&diagnostics));
+            } else {
+                for diagnostic in &diagnostics {
+                    println!("{}: {}", input.input.display(), diagnostic);
                 }
-                NoteEvent::ComplexSkidEnd {
-                    id: end_id,
-                    lane_start,
-                    width_start,
-                    lane_end,
-                    width_end,
-                } => {
-                    if id == end_id {
-                        waypoints.push(LongPoint::SkidComplex {
-                            point_time: measure_tick_to_ms(
-                                measure_num as u32,
-                                end_tick_num as u32,
-                                bpm,
-                            ),
-                            pos_left_start: *lane_start as u32 * 4096,
-                            pos_right_start: (*lane_start + width_start) as u32 * 4096,
-                            pos_left_end: *lane_end as u32 * 4096,
-                            pos_right_end: (*lane_end + width_end) as u32 * 4096,
-                        });
-                        return measure_tick_to_ms(measure_num as u32, end_tick_num as u32, bpm);
-                    }
+            }
+            let errors = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+                .count();
+            if verbosity >= Verbosity::Normal {
+                eprintln!(
+                    "{}: {} errors, {} warnings",
+                    input.input.display(),
+                    errors,
+                    diagnostics.len() - errors
+                );
+            }
+            if errors > 0 {
+                return Err(format!("{}: chart has lint errors", input.input.display()).into());
+            }
+        }
+        Command::Stats { input, json } => {
+            let chart = load(&input, verbosity)?;
+            let stats = stats(&chart).map_err(|e| format!("{}: {}", input.input.display(), e))?;
+            if json {
+                print!("{}", stats.to_json());
+            } else {
+                print!("{}", stats);
+            }
+        }
+        Command::Render {
+            input,
+            output,
+            measures,
+            format,
+        } => {
+            let chart = load(&input, verbosity)?;
+            let pages = chart
+                .to_svg_pages(measures as usize)
+                .map_err(|e| format!("{}: {}", input.input.display(), e))?;
+            if pages.len() > 1 && is_stdio(&output) {
+                return Err("the chart takes more than one page; give a file with -o".into());
+            }
+            for (index, page) in pages.iter().enumerate() {
+                let path = if pages.len() > 1 {
+                    numbered_path(&output, index + 1)
+                } else {
+                    output.clone()
+                };
+                let image = match format {
+                    ImageFormat::Svg => page.clone().into_bytes(),
+                    #[cfg(feature = "png")]
+                    ImageFormat::Png => svg_to_png(page)?,
+                };
+                write_output(&path, &image)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                if verbosity >= Verbosity::Normal && !is_stdio(&path) {
+                    eprintln!("wrote {}", path.display());
                 }
-                _ => {}
             }
         }
-    }
-    time
-}
-
-fn handle_event(
-    event: &NoteEvent,
-    measure_num: usize,
-    tick_num: usize,
-    bpm: u32,
-    sequence_data: &mut XMLElement,
-    measure: &Measure,
-    measures: &Vec<Measure>,
-) {
-    match event {
-        NoteEvent::LeftStep { lane, width } | NoteEvent::RightStep { lane, width } => {
-            let mut step = XMLElement::new("step");
-            let time = measure_tick_to_ms(measure_num as u32, tick_num as u32, bpm);
-
-            add_s64_element(&mut step, "stime_ms", time.into());
-            add_s64_element(&mut step, "etime_ms", time.into());
-            add_s32_element(&mut step, "stime_dt", ms_to_dt(time, bpm).into());
-            add_s32_element(&mut step, "etime_dt", ms_to_dt(time, bpm).into());
-            add_s32_element(&mut step, "category", 0);
-            add_s32_element(&mut step, "pos_left", (*lane as u32 * 4096).into());
-            add_s32_element(
-                &mut step,
-                "pos_right",
-                ((lane + width) as u32 * 4096).into(),
-            );
-            add_s32_element(
-                &mut step,
-                "kind",
-                match event {
-                    NoteEvent::LeftStep { .. } => 1,
-                    NoteEvent::RightStep { .. } => 2,
-                    _ => panic!(),
-                },
-            );
-            add_s32_element(&mut step, "var", 0);
-            add_s32_element(&mut step, "player_id", 0);
-
-            sequence_data.add_child(step).unwrap();
+        Command::View {
+            input,
+            measure,
+            measures,
+            ascii,
+        } => {
+            let chart = load(&input, verbosity)?;
+            let style = if ascii {
+                GridStyle::Ascii
+            } else {
+                GridStyle::Unicode
+            };
+            let view = GridView::new(&chart, style)
+                .map_err(|e| format!("{}: {}", input.input.display(), e))?;
+            let interactive = std::io::stdout().is_terminal()
+                && std::io::stdin().is_terminal()
+                && !is_stdio(&input.input);
+            if interactive {
+                let per_page = measures.map_or(VIEW_MEASURES, |measures| measures as usize);
+                page_through(&view, measure, per_page)?;
+            } else {
+                let end = match measures {
+                    Some(measures) => measure + measures as usize,
+                    None => view.measure_count().max(measure + 1),
+                };
+                print!("{}", view.page(measure..end));
+            }
         }
-        NoteEvent::Jump | NoteEvent::Down => {
-            let mut step = XMLElement::new("step");
-            let time = measure_tick_to_ms(measure_num as u32, tick_num as u32, bpm);
-
-            add_s64_element(&mut step, "stime_ms", time.into());
-            add_s64_element(&mut step, "etime_ms", time.into());
-            add_s32_element(&mut step, "stime_dt", ms_to_dt(time, bpm).into());
-            add_s32_element(&mut step, "etime_dt", ms_to_dt(time, bpm).into());
-            add_s32_element(&mut step, "category", 0);
-            add_s32_element(&mut step, "pos_left", 0);
-            add_s32_element(&mut step, "pos_right", 65536);
-            add_s32_element(
-                &mut step,
-                "kind",
-                match event {
-                    NoteEvent::Down => 3,
-                    NoteEvent::Jump => 4,
-                    _ => panic!(),
-                },
-            );
-            add_s32_element(&mut step, "var", 0);
-            add_s32_element(&mut step, "player_id", 4);
-
-            sequence_data.add_child(step).unwrap();
+        Command::Fmt { input, output } => {
+            let mut chart = load(&input, verbosity)?;
+            chart
+                .normalize()
+                .map_err(|e| format!("{}: {}", input.input.display(), e))?;
+            write_output(&output, chart.to_ssf()?.as_bytes())?;
         }
-        NoteEvent::LeftHoldStart { id, lane, width }
-        | NoteEvent::RightHoldStart { id, lane, width } => {
-            let mut step = XMLElement::new("step");
-            let time = measure_tick_to_ms(measure_num as u32, tick_num as u32, bpm);
-            let mut end_time;
-
-            let mut waypoints = Vec::<LongPoint>::new();
-
-            end_time = find_end(
-                measure,
-                tick_num,
-                id,
-                &mut waypoints,
-                bpm,
-                measure_num,
-                time,
-            );
-
-            if end_time == time {
-                for (end_measure_num, end_measure) in
-                    measures.iter().enumerate().skip(measure_num + 1)
-                {
-                    end_time = find_end(
-                        end_measure,
-                        0,
-                        id,
-                        &mut waypoints,
-                        bpm,
-                        end_measure_num,
-                        time,
-                    );
-                    if end_time != time {
-                        break;
+        #[cfg(feature = "serde")]
+        Command::ExportJson { input, output } => {
+            let chart = load(&input, verbosity)?;
+            let json = chart
+                .to_json()
+                .map_err(|e| format!("{}: {}", input.input.display(), e))?;
+            write_output(&output, json.as_bytes())?;
+        }
+        #[cfg(feature = "serde")]
+        Command::ImportJson { input, output } => {
+            let chart = load_json(&input)?;
+            if verbosity >= Verbosity::Verbose {
+                dump_ticks(&chart);
+            }
+            write_output(&output, chart.to_ssf()?.as_bytes())?;
+        }
+        Command::ImportDrs { input, output } => {
+            let mut source =
+                read_input_bytes(&input).map_err(|e| format!("{}: {}", input.display(), e))?;
+            if source.first() == Some(&0xA0) {
+                source = kbin_to_xml(&source)
+                    .map_err(|e| format!("{}: {}", input.display(), e))?
+                    .into_bytes();
+            }
+            let source = String::from_utf8(source)
+                .map_err(|e| format!("{}: {}", input.display(), e))?;
+            let chart =
+                parse_drs_xml(&source).map_err(|e| format!("{}: {}", input.display(), e))?;
+            if verbosity >= Verbosity::Verbose {
+                dump_ticks(&chart);
+            }
+            write_output(&output, chart.to_ssf()?.as_bytes())?;
+        }
+        Command::ImportSus { input, output } => {
+            let chart = load_sus(&input, verbosity)?;
+            if verbosity >= Verbosity::Verbose {
+                dump_ticks(&chart);
+            }
+            write_output(&output, chart.to_ssf()?.as_bytes())?;
+        }
+        Command::Batch {
+            input,
+            output,
+            format,
+            jobs,
+        } => {
+            let mut charts = Vec::new();
+            find_charts(&input, &mut charts)
+                .map_err(|e| format!("{}: {}", input.display(), e))?;
+            let jobs = jobs.unwrap_or_else(|| {
+                std::thread::available_parallelism().map_or(1, |jobs| jobs.get())
+            });
+            let relative = |path: &Path| path.strip_prefix(&input).unwrap_or(path).to_path_buf();
+
+            let converted = parallel_map(&charts, jobs, |chart| {
+                let relative_dir = relative(chart)
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default();
+                convert_for_batch(chart, &relative_dir, format)
+            });
+
+            let mut written = HashMap::<PathBuf, PathBuf>::new();
+            let mut rows = Vec::new();
+            for (chart, result) in charts.iter().zip(converted) {
+                let chart = relative(chart);
+                let result = result.and_then(|converted| converted);
+                let result = result.and_then(|(path, contents)| {
+                    if let Some(other) = written.get(&path) {
+                        return Err(format!("same SONGID and DIFFICULTY as {}", other.display()));
                     }
-                }
+                    let destination = output.join(&path);
+                    if let Some(parent) = destination.parent() {
+                        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                    }
+                    std::fs::write(&destination, contents)
+                        .map_err(|e| format!("{}: {}", destination.display(), e))?;
+                    written.insert(path.clone(), chart.clone());
+                    Ok(path)
+                });
+                rows.push((chart, result));
             }
 
-            add_s64_element(&mut step, "stime_ms", time.into());
-            add_s64_element(&mut step, "etime_ms", end_time.into());
-            add_s32_element(&mut step, "stime_dt", ms_to_dt(time, bpm).into());
-            add_s32_element(&mut step, "etime_dt", ms_to_dt(end_time, bpm).into());
-            add_s32_element(&mut step, "category", 1);
-            add_s32_element(&mut step, "pos_left", (*lane as u32 * 4096).into());
-            add_s32_element(
-                &mut step,
-                "pos_right",
-                ((lane + width) as u32 * 4096).into(),
-            );
-            add_s32_element(
-                &mut step,
-                "kind",
-                match event {
-                    NoteEvent::LeftHoldStart { .. } => 1,
-                    NoteEvent::RightHoldStart { .. } => 2,
-                    _ => panic!(),
-                },
-            );
-            add_s32_element(&mut step, "var", 0);
-            add_s32_element(&mut step, "player_id", 0);
-
-            let mut long_point = XMLElement::new("long_point");
-
-            let mut last_left = *lane as u32 * 4096;
-            let mut last_right = (*lane + width) as u32 * 4096;
-
-            for waypoint in waypoints {
-                let mut point = XMLElement::new("point");
-                match waypoint {
-                    LongPoint::Normal {
-                        point_time,
-                        pos_left,
-                        pos_right,
-                    } => {
-                        add_s64_element(&mut point, "point_time", point_time.into());
-                        add_s32_element(&mut point, "pos_left", pos_left);
-                        add_s32_element(&mut point, "pos_right", pos_right);
-                    }
-                    LongPoint::SkidComplex {
-                        point_time,
-                        pos_left_start,
-                        pos_right_start,
-                        pos_left_end,
-                        pos_right_end,
-                    } => {
-                        add_s64_element(&mut point, "point_time", point_time.into());
-                        add_s32_element(&mut point, "pos_left", pos_left_start);
-                        add_s32_element(&mut point, "pos_right", pos_right_start);
-                        add_s32_element(&mut point, "pos_lend", pos_left_end);
-                        add_s32_element(&mut point, "pos_rend", pos_right_end);
-                        last_left = pos_left_end;
-                        last_right = pos_right_end;
+            let failed = rows.iter().filter(|(_, result)| result.is_err()).count();
+            let width = rows
+                .iter()
+                .map(|(chart, _)| chart.display().to_string().len())
+                .max()
+                .unwrap_or(0)
+                .max("chart".len());
+            if verbosity >= Verbosity::Normal {
+                println!("{:<6}  {:<width$}  result", "status", "chart");
+            }
+            for (chart, result) in &rows {
+                match result {
+                    Ok(path) if verbosity >= Verbosity::Normal => {
+                        println!("{:<6}  {:<width$}  {}", "ok", chart.display(), path.display())
                     }
-                    LongPoint::SkidSimple {
-                        point_time,
-                        pos_left,
-                        pos_right,
-                        is_final,
-                    } => {
-                        add_s64_element(&mut point, "point_time", point_time.into());
-                        add_s32_element(&mut point, "pos_left", last_left);
-                        add_s32_element(&mut point, "pos_right", last_right);
-                        if is_final {
-                            if pos_right > last_right {
-                                add_s32_element(&mut point, "pos_lend", (pos_left + pos_right) / 2);
-                                add_s32_element(&mut point, "pos_rend", pos_right);
-                            } else {
-                                add_s32_element(&mut point, "pos_lend", pos_left);
-                                add_s32_element(&mut point, "pos_rend", (pos_left + pos_right) / 2);
-                            }
-                        } else {
-                            add_s32_element(&mut point, "pos_lend", pos_left);
-                            add_s32_element(&mut point, "pos_rend", pos_right);
-                        }
-                        last_left = pos_left;
-                        last_right = pos_right;
+                    Ok(_) => {}
+                    Err(error) => {
+                        println!("{:<6}  {:<width$}  {}", "failed", chart.display(), error)
                     }
                 }
-                long_point.add_child(point).unwrap();
             }
-            step.add_child(long_point).unwrap();
-
-            sequence_data.add_child(step).unwrap();
+            if verbosity >= Verbosity::Normal {
+                println!("{} converted, {} failed", rows.len() - failed, failed);
+            }
+            if failed > 0 {
+                return Err(format!("{} of {} charts failed", failed, rows.len()).into());
+            }
         }
-        _ => {}
-    }
-}
-
-fn main() {
-    print!("\x1B[2J\x1B[1;1H");
-    let input = std::fs::read_to_string("test.ssf").unwrap();
-    let mut input_lines = input.lines();
-
-    let mut bpm = 0;
-
-    loop {
-        let line_raw = input_lines.next().unwrap();
-        if !line_raw.starts_with("#") {
-            continue;
-      }
-        let line = line_raw.strip_prefix("#").unwrap();
-        if let Some((command, argument)) = line.split_once(" ") {
-            match command {
-                "TITLE" => {
-                    println!("Title: {}", argument);
+        Command::Package {
+            inputs,
+            output,
+            song_id,
+            format,
+        } => {
+            let mut paths = Vec::new();
+            for input in &inputs {
+                if input.is_dir() {
+                    find_charts(input, &mut paths)
+                        .map_err(|e| format!("{}: {}", input.display(), e))?;
+                } else {
+                    paths.push(input.clone());
                 }
-                "ARTIST" => {
-                    println!("Artist: {}", argument);
+            }
+            let mut songs = BTreeMap::<String, Vec<(PathBuf, Chart)>>::new();
+            for path in paths {
+                let chart = load_path(&path, verbosity)?;
+                let id = chart
+                    .header
+                    .song_id
+                    .clone()
+                    .ok_or_else(|| format!("{}: chart has no SONGID", path.display()))?;
+                songs.entry(id).or_default().push((path, chart));
+            }
+
+            let (song_id, charts) = match song_id {
+                Some(song_id) => {
+                    let charts = songs
+                        .remove(&song_id)
+                        .ok_or_else(|| format!("no charts for SONGID {}", song_id))?;
+                    (song_id, charts)
                 }
-                "DESIGNER" => {
-                    println!("Designer: {}", argument);
+                None if songs.len() == 1 => songs.pop_first().unwrap(),
+                None => {
+                    let ids: Vec<_> = songs.keys().map(String::as_str).collect();
+                    return Err(format!(
+                        "charts for more than one SONGID ({}), pick one with --song-id",
+                        ids.join(", ")
+                    )
+                    .into());
                 }
-                "DIFFICULTY" => {
-                    println!(
-                        "Difficulty: {}",
-                        match argument {
-                            "0" => "Easy",
-                            "1" => "Normal",
-                            "2" => "Hard",
-                            _ => panic!(),
+            };
+            let (paths, charts): (Vec<_>, Vec<_>) = charts.into_iter().unzip();
+            check_song(&song_id, &charts)?;
+
+            // Convert everything and check the assets before writing, so a
+            // failure leaves nothing behind.
+            let mut sequences = Vec::new();
+            for (path, chart) in paths.iter().zip(&charts) {
+                let xml = chart
+                    .to_drs_xml()
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                let name = sequence_name(&song_id, chart.header.difficulty.unwrap());
+                sequences.push((format!("{}.xml", name), encode_xml(xml, format)?));
+            }
+
+            // Every difficulty shares the wave file, but each may have its
+            // own jacket.
+            let mut assets = BTreeMap::<PathBuf, PathBuf>::new();
+            for (path, chart) in paths.iter().zip(&charts) {
+                for asset in [&chart.header.wave, &chart.header.jacket].into_iter().flatten() {
+                    let source = path.parent().unwrap_or(Path::new("")).join(asset);
+                    if !source.is_file() {
+                        let missing = format!("{}: missing {}", path.display(), source.display());
+                        return Err(missing.into());
+                    }
+                    let name = PathBuf::from(source.file_name().unwrap_or_default());
+                    match assets.get(&name) {
+                        Some(copied) if *copied != source => {
+                            return Err(format!(
+                                "{} and {} would both be copied as {}",
+                                copied.display(),
+                                source.display(),
+                                name.display()
+                            )
+                            .into())
                         }
-                    );
-                }
-                "PLAYLEVEL" => {
-                    println!("Level: {}", argument);
-                }
-                "SONGID" => {
-                    println!("Song ID: {}", argument);
-                }
-                "WAVE" => {
-                    println!("Sound File: {}", argument);
-                }
-                "WAVEOFFSET" => {
-                    println!("Sound Offset: {}", argument);
-                }
-                "JACKET" => {
-                    println!("Cover Image: {}", argument);
-                }
-                "BPM01:" => {
-                    println!("BPM: {}", argument);
-                    bpm = argument.parse::<u32>().unwrap();
-                }
-                "00008:" => {
-                    println!("Padding Bars: {}", argument);
+                        _ => {
+                            assets.insert(name, source);
+                        }
+                    }
                 }
-                _ => {}
             }
-        } else {
-            break;
-        }
-    }
 
-    let mut measures = Vec::<Measure>::new();
-    let mut current_measure = 0;
+            let dir = output.join(&song_id);
+            std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+            let mut written = Vec::new();
+            for (name, contents) in sequences {
+                let destination = dir.join(name);
+                write_output(&destination, &contents)
+                    .map_err(|e| format!("{}: {}", destination.display(), e))?;
+                written.push(destination);
+            }
+            for (name, source) in assets {
+                let destination = dir.join(name);
+                std::fs::copy(&source, &destination)
+                    .map_err(|e| format!("{}: {}", source.display(), e))?;
+                written.push(destination);
+            }
 
-    loop {
-        if let Some(line) = input_lines.next() {
-            if line.contains("END") {
-                break;
-            }
-            if !line.contains(":") {
-                current_measure = line.parse::<usize>().unwrap();
-                while measures.len() < current_measure + 1 {
-                    measures.push(Measure::new());
+            if verbosity >= Verbosity::Normal {
+                for path in written {
+                    eprintln!("wrote {}", path.display());
                 }
-            } else {
-                let (tick, notes) = line.split_once(":").unwrap();
-                for note_string in notes.split(",") {
-                    if note_string.len() == 0 {
-                        continue;
-                    }
-                    measures[current_measure].ticks[tick.parse::<usize>().unwrap()]
-                        .push(NoteEvent::from_string(note_string.to_string()));
-                }
-                //                 println!("Measure: {}, Tick: {}, Notes: {:?}", current_measure, tick, measures[current_measure].ticks[tick.parse::<usize>().unwrap()]);
             }
-        } else {
-            break;
+        }
+        Command::MusicList {
+            inputs,
+            output,
+            format,
+        } => {
+            let charts = inputs
+                .iter()
+                .map(|path| load_path(path, verbosity))
+                .collect::<Result<Vec<_>, _>>()?;
+            write_output(&output, &encode_xml(music_list_xml(&charts)?, format)?)?;
         }
     }
+    Ok(())
+}
 
-    let mut builder = XMLBuilder::new()
-        .version(XMLVersion::XML1_0)
-        .encoding("UTF-8".into())
-        .build();
-
-    let mut data = xml_boilerplate(bpm * 100);
-
-    let mut sequence_data = XMLElement::new("sequence_data");
-
-    for (measure_num, measure) in measures.iter().enumerate() {
-        for (tick_num, tick) in measure.ticks.iter().enumerate() {
-            if tick.len() == 0 {
-                continue;
-            }
-            println!(
-                "Measure: {:?}, Tick: {:?}, Notes: {:?}",
-                measure_num, tick_num, tick
-            );
-            println!(
-                "ms: {:?}",
-                measure_tick_to_ms(measure_num as u32, tick_num as u32, bpm)
-            );
-            for event in tick {
-                handle_event(
-                    event,
-                    measure_num,
-                    tick_num,
-                    bpm,
-                    &mut sequence_data,
-                    measure,
-                    &measures,
-                );
-            }
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
         }
     }
-
-    data.add_child(sequence_data).unwrap();
-
-    builder.set_root_element(data);
-    let mut writer = Vec::<u8>::new();
-    builder.generate(&mut writer).unwrap();
-    std::fs::write("output.xml", writer).unwrap();
 }
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use xml_builder::XMLElement;
+
+use crate::chart::{Chart, Difficulty};
+use crate::error::MetadataError;
+use crate::xml::{add_s32_element, add_str_element, add_u32_element, add_u8_element, render};
+
+/// The code the game uses for each SSF difficulty.
+fn fumen_code(difficulty: Difficulty) -> &'static str {
+    match difficulty {
+        Difficulty::Easy => "1a",
+        Difficulty::Normal => "1b",
+        Difficulty::Hard => "1c",
+    }
+}
+
+/// The `difficulty` child each SSF difficulty is listed under.
+fn fumen_name(difficulty: Difficulty) -> String {
+    format!("fumen_{}", fumen_code(difficulty))
+}
+
+/// The file name, without extension, the game loads one difficulty's
+/// sequence from, such as `1234_1c`.
+pub fn sequence_name(song_id: &str, difficulty: Difficulty) -> String {
+    format!("{}_{}", song_id, fumen_code(difficulty))
+}
+
+/// Checks that a SONGID is safe to name files and folders after: one plain
+/// file name, so output can't land outside the folder it's written to.
+pub fn check_song_id(song_id: &str) -> Result<(), MetadataError> {
+    let plain = !song_id.is_empty()
+        && !song_id.contains(['/', '\\', ':', '\0'])
+        && !song_id.contains("..")
+        && Path::new(song_id).components().count() == 1
+        && !Path::new(song_id).is_absolute();
+    if plain {
+        Ok(())
+    } else {
+        Err(MetadataError::InvalidSongId {
+            song_id: song_id.to_string(),
+        })
+    }
+}
+
+/// Reads the level number from `PLAYLEVEL`, ignoring a trailing `+`.
+fn level_number(level: &str) -> Option<i32> {
+    level.strip_suffix('+').unwrap_or(level).parse().ok()
+}
+
+/// The chart's BPM, or its lowest and highest BPM as `min-max` when it
+/// changes tempo.
+pub fn bpm_range(chart: &Chart) -> String {
+    let (min, max) = (chart.tempo.min_bpm(), chart.tempo.max_bpm());
+    if min == max {
+        min.to_string()
+    } else {
+        format!("{}-{}", min, max)
+    }
+}
+
+/// A header field every difficulty of a song must agree on, and how to read
+/// it.
+type SharedField = (&'static str, fn(&Chart) -> String);
+
+/// Checks that the charts of one song are one of each difficulty and agree
+/// on title, artist, BPM and wave file, and that the SONGID passes
+/// [`check_song_id`].
+pub fn check_song(song_id: &str, charts: &[Chart]) -> Result<(), MetadataError> {
+    check_song_id(song_id)?;
+    let mut difficulties = Vec::new();
+    for chart in charts {
+        let difficulty = chart
+            .header
+            .difficulty
+            .ok_or_else(|| MetadataError::MissingDifficulty {
+                song_id: song_id.to_string(),
+            })?;
+        if difficulties.contains(&difficulty) {
+            return Err(MetadataError::DuplicateDifficulty {
+                song_id: song_id.to_string(),
+                difficulty,
+            });
+        }
+        difficulties.push(difficulty);
+    }
+
+    let shared: [SharedField; 4] = [
+        ("TITLE", |chart| chart.header.title.clone().unwrap_or_default()),
+        ("ARTIST", |chart| chart.header.artist.clone().unwrap_or_default()),
+        ("BPM", bpm_range),
+        ("WAVE", |chart| chart.header.wave.clone().unwrap_or_default()),
+    ];
+    for (field, value) in shared {
+        let Some(first) = charts.first().map(value) else {
+            break;
+        };
+        if let Some(other) = charts.iter().map(value).find(|other| *other != first) {
+            return Err(MetadataError::Mismatch {
+                song_id: song_id.to_string(),
+                field,
+                first,
+                other,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn music_entry(song_id: &str, charts: &[&Chart]) -> Result<XMLElement, MetadataError> {
+    let mut by_difficulty = BTreeMap::new();
+    for chart in charts {
+        let difficulty = chart
+            .header
+            .difficulty
+            .ok_or_else(|| MetadataError::MissingDifficulty {
+                song_id: song_id.to_string(),
+            })?;
+        if by_difficulty.insert(fumen_name(difficulty), *chart).is_some() {
+            return Err(MetadataError::DuplicateDifficulty {
+                song_id: song_id.to_string(),
+                difficulty,
+            });
+        }
+    }
+
+    let first_with = |field: fn(&Chart) -> Option<&String>| {
+        charts
+            .iter()
+            .find_map(|chart| field(chart))
+            .map_or("", |value| value.as_str())
+    };
+
+    let mut music = XMLElement::new("music");
+    music.add_attribute("id", song_id);
+
+    let mut info = XMLElement::new("info");
+    add_str_element(&mut info, "title_name", first_with(|c| c.header.title.as_ref()));
+    add_str_element(&mut info, "artist_name", first_with(|c| c.header.artist.as_ref()));
+    let bpm_max = charts.iter().map(|c| c.tempo.max_bpm()).max().unwrap();
+    let bpm_min = charts.iter().map(|c| c.tempo.min_bpm()).min().unwrap();
+    add_u32_element(&mut info, "bpm_max", bpm_max.0);
+    add_u32_element(&mut info, "bpm_min", bpm_min.0);
+    music.add_child(info).unwrap();
+
+    let mut difficulty = XMLElement::new("difficulty");
+    for (name, chart) in by_difficulty {
+        let level = chart.header.play_level.as_deref().unwrap_or("0");
+        let mut fumen = XMLElement::new(&name);
+        add_s32_element(
+            &mut fumen,
+            "difnum",
+            level_number(level).ok_or_else(|| MetadataError::InvalidLevel {
+                song_id: song_id.to_string(),
+                level: level.to_string(),
+            })?,
+        );
+        add_str_element(
+            &mut fumen,
+            "designer",
+            chart.header.designer.as_deref().unwrap_or(""),
+        );
+        add_u8_element(&mut fumen, "playable", 1);
+        difficulty.add_child(fumen).unwrap();
+    }
+    music.add_child(difficulty).unwrap();
+
+    Ok(music)
+}
+
+/// Builds the music list XML for a set of charts, with one `<music>` entry
+/// per `SONGID` holding every difficulty of that song.
+pub fn music_list_xml(charts: &[Chart]) -> Result<String, MetadataError> {
+    let mut songs = BTreeMap::<&str, Vec<&Chart>>::new();
+    for chart in charts {
+        let song_id = chart
+            .header
+            .song_id
+            .as_deref()
+            .ok_or_else(|| MetadataError::MissingSongId {
+                title: chart.header.title.clone(),
+            })?;
+        songs.entry(song_id).or_default().push(chart);
+    }
+
+    let mut mdb = XMLElement::new("mdb");
+    for (song_id, charts) in songs {
+        mdb.add_child(music_entry(song_id, &charts)?).unwrap();
+    }
+    Ok(render(mdb))
+}
@@ -0,0 +1,312 @@
+use crate::error::{ParseError, ParseErrorKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+pub enum NoteEvent {
+    LeftStep {
+        lane: u8,
+        width: u8,
+    },
+    RightStep {
+        lane: u8,
+        width: u8,
+    },
+    Jump,
+    Down,
+    LeftHoldStart {
+        id: u8,
+        lane: u8,
+        width: u8,
+    },
+    RightHoldStart {
+        id: u8,
+        lane: u8,
+        width: u8,
+    },
+    SlideWaypoint {
+        id: u8,
+        lane: u8,
+        width: u8,
+    },
+    SlideEnd {
+        id: u8,
+        lane: u8,
+        width: u8,
+    },
+    SimpleSkidWaypoint {
+        id: u8,
+        lane: u8,
+        width: u8,
+    },
+    ComplexSkidWaypoint {
+        id: u8,
+        lane_start: u8,
+        width_start: u8,
+        lane_end: u8,
+        width_end: u8,
+    },
+    SimpleSkidEnd {
+        id: u8,
+        lane: u8,
+        width: u8,
+    },
+    ComplexSkidEnd {
+        id: u8,
+        lane_start: u8,
+        width_start: u8,
+        lane_end: u8,
+        width_end: u8,
+    },
+}
+
+/// Reads the single digit at `index` of a note string.
+fn digit(chars: &[char], index: usize, radix: u32, field: &'static str) -> Result<u8, ParseError> {
+    let Some(digit_char) = chars.get(index) else {
+        let token: String = chars.iter().collect();
+        return Err(ParseError::new(
+            1,
+            1,
+            &token,
+            ParseErrorKind::MissingDigit(field),
+        ));
+    };
+    digit_char.to_digit(radix).map(|digit| digit as u8).ok_or_else(|| {
+        ParseError::new(
+            1,
+            index + 1,
+            &digit_char.to_string(),
+            ParseErrorKind::InvalidDigit(field),
+        )
+    })
+}
+
+impl NoteEvent {
+    /// Decodes one comma-separated note code, e.g. `003` or `41A3`.
+    ///
+    /// Errors are located as if `input_string` were the whole of line 1.
+    pub fn from_string(input_string: &str) -> Result<NoteEvent, ParseError> {
+        let chars: Vec<char> = input_string.chars().collect();
+        let lane = |index| digit(&chars, index, 16, "lane");
+        let width = |index| digit(&chars, index, 16, "width").map(|width| width + 1);
+        let id = || digit(&chars, 1, 36, "hold id");
+        let Some(&note_type) = chars.first() else {
+            return Err(ParseError::new(
+                1,
+                1,
+                "",
+                ParseErrorKind::MissingDigit("note type"),
+            ));
+        };
+        let event = match note_type {
+            '0' => NoteEvent::LeftStep {
+                lane: lane(1)?,
+                width: width(2)?,
+            },
+            '1' => NoteEvent::RightStep {
+                lane: lane(1)?,
+                width: width(2)?,
+            },
+            '2' => NoteEvent::Jump,
+            '3' => NoteEvent::Down,
+            '4' => NoteEvent::LeftHoldStart {
+                id: id()?,
+                lane: lane(2)?,
+                width: width(3)?,
+            },
+            '5' => NoteEvent::RightHoldStart {
+                id: id()?,
+                lane: lane(2)?,
+                width: width(3)?,
+            },
+            '6' => NoteEvent::SlideWaypoint {
+                id: id()?,
+                lane: lane(2)?,
+                width: width(3)?,
+            },
+            '7' => NoteEvent::SlideEnd {
+                id: id()?,
+                lane: lane(2)?,
+                width: width(3)?,
+            },
+            '8' => NoteEvent::SimpleSkidWaypoint {
+                id: id()?,
+                lane: lane(2)?,
+                width: width(3)?,
+            },
+            '9' => NoteEvent::ComplexSkidWaypoint {
+                id: id()?,
+                lane_start: lane(2)?,
+                width_start: width(3)?,
+                lane_end: lane(4)?,
+                width_end: width(5)?,
+            },
+            'A' => NoteEvent::SimpleSkidEnd {
+                id: id()?,
+                lane: lane(2)?,
+                width: width(3)?,
+            },
+            'B' => NoteEvent::ComplexSkidEnd {
+                id: id()?,
+                lane_start: lane(2)?,
+                width_start: width(3)?,
+                lane_end: lane(4)?,
+                width_end: width(5)?,
+            },
+            _ => {
+                return Err(ParseError::new(
+                    1,
+                    1,
+                    &note_type.to_string(),
+                    ParseErrorKind::UnknownNoteType(note_type),
+                ))
+            }
+        };
+        let length = match event {
+            NoteEvent::Jump | NoteEvent::Down => 1,
+            NoteEvent::LeftStep { .. } | NoteEvent::RightStep { .. } => 3,
+            NoteEvent::ComplexSkidWaypoint { .. } | NoteEvent::ComplexSkidEnd { .. } => 6,
+            _ => 4,
+        };
+        if chars.len() > length {
+            let rest: String = chars[length..].iter().collect();
+            return Err(ParseError::new(
+                1,
+                length + 1,
+                &rest,
+                ParseErrorKind::TrailingCharacters,
+            ));
+        }
+        Ok(event)
+    }
+}
+
+fn hex(value: u8) -> Option<char> {
+    char::from_digit(value as u32, 16).map(|c| c.to_ascii_uppercase())
+}
+
+fn base36(value: u8) -> Option<char> {
+    char::from_digit(value as u32, 36).map(|c| c.to_ascii_uppercase())
+}
+
+impl NoteEvent {
+    /// Encodes the note the way `from_string` decodes it, or `None` when a
+    /// lane, width or hold id does not fit in one digit.
+    pub fn to_code(&self) -> Option<String> {
+        let lane = hex;
+        let width = |width: u8| width.checked_sub(1).and_then(hex);
+        let chars = match *self {
+            NoteEvent::LeftStep { lane: l, width: w } => vec![Some('0'), lane(l), width(w)],
+            NoteEvent::RightStep { lane: l, width: w } => vec![Some('1'), lane(l), width(w)],
+            NoteEvent::Jump => vec![Some('2')],
+            NoteEvent::Down => vec![Some('3')],
+            NoteEvent::LeftHoldStart {
+                id,
+                lane: l,
+                width: w,
+            } => vec![Some('4'), base36(id), lane(l), width(w)],
+            NoteEvent::RightHoldStart {
+                id,
+                lane: l,
+                width: w,
+            } => vec![Some('5'), base36(id), lane(l), width(w)],
+            NoteEvent::SlideWaypoint {
+                id,
+                lane: l,
+                width: w,
+            } => vec![Some('6'), base36(id), lane(l), width(w)],
+            NoteEvent::SlideEnd {
+                id,
+                lane: l,
+                width: w,
+            } => vec![Some('7'), base36(id), lane(l), width(w)],
+            NoteEvent::SimpleSkidWaypoint {
+                id,
+                lane: l,
+                width: w,
+            } => vec![Some('8'), base36(id), lane(l), width(w)],
+            NoteEvent::ComplexSkidWaypoint {
+                id,
+                lane_start,
+                width_start,
+                lane_end,
+                width_end,
+            } => vec![
+                Some('9'),
+                base36(id),
+                lane(lane_start),
+                width(width_start),
+                lane(lane_end),
+                width(width_end),
+            ],
+            NoteEvent::SimpleSkidEnd {
+                id,
+                lane: l,
+                width: w,
+            } => vec![Some('A'), base36(id), lane(l), width(w)],
+            NoteEvent::ComplexSkidEnd {
+                id,
+                lane_start,
+                width_start,
+                lane_end,
+                width_end,
+            } => vec![
+                Some('B'),
+                base36(id),
+                lane(lane_start),
+                width(width_start),
+                lane(lane_end),
+                width(width_end),
+            ],
+        };
+        chars.into_iter().collect()
+    }
+
+    /// The lanes a note covers, as `(lane, width)`; complex skids cover one
+    /// span where they start and another where they end.
+    pub(crate) fn spans(&self) -> Vec<(u8, u8)> {
+        match *self {
+            NoteEvent::Jump | NoteEvent::Down => vec![],
+            NoteEvent::LeftStep { lane, width }
+            | NoteEvent::RightStep { lane, width }
+            | NoteEvent::LeftHoldStart { lane, width, .. }
+            | NoteEvent::RightHoldStart { lane, width, .. }
+            | NoteEvent::SlideWaypoint { lane, width, .. }
+            | NoteEvent::SlideEnd { lane, width, .. }
+            | NoteEvent::SimpleSkidWaypoint { lane, width, .. }
+            | NoteEvent::SimpleSkidEnd { lane, width, .. } => vec![(lane, width)],
+            NoteEvent::ComplexSkidWaypoint {
+                lane_start,
+                width_start,
+                lane_end,
+                width_end,
+                ..
+            }
+            | NoteEvent::ComplexSkidEnd {
+                lane_start,
+                width_start,
+                lane_end,
+                width_end,
+                ..
+            } => vec![(lane_start, width_start), (lane_end, width_end)],
+        }
+    }
+    /// The id of the hold the note belongs to, or `None` for steps, jumps
+    /// and downs.
+    pub fn hold_id(&self) -> Option<u8> {
+        match *self {
+            NoteEvent::LeftStep { .. }
+            | NoteEvent::RightStep { .. }
+            | NoteEvent::Jump
+            | NoteEvent::Down => None,
+            NoteEvent::LeftHoldStart { id, .. }
+            | NoteEvent::RightHoldStart { id, .. }
+            | NoteEvent::SlideWaypoint { id, .. }
+            | NoteEvent::SlideEnd { id, .. }
+            | NoteEvent::SimpleSkidWaypoint { id, .. }
+            | NoteEvent::ComplexSkidWaypoint { id, .. }
+            | NoteEvent::SimpleSkidEnd { id, .. }
+            | NoteEvent::ComplexSkidEnd { id, .. } => Some(id),
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// An exact fraction, always stored in lowest terms with a positive
+/// denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numer: i128,
+    denom: i128,
+}
+
+/// Greatest common divisor of `a` and `b`, ignoring their signs.
+pub(crate) fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational { numer: 0, denom: 1 };
+
+    pub fn new(numer: i128, denom: i128) -> Rational {
+        assert!(denom != 0, "rational with zero denominator");
+        let divisor = gcd(numer, denom).max(1) * denom.signum();
+        Rational {
+            numer: numer / divisor,
+            denom: denom / divisor,
+        }
+    }
+
+    pub fn integer(value: i128) -> Rational {
+        Rational {
+            numer: value,
+            denom: 1,
+        }
+    }
+
+    pub fn numer(&self) -> i128 {
+        self.numer
+    }
+
+    pub fn denom(&self) -> i128 {
+        self.denom
+    }
+
+    pub fn floor(&self) -> i128 {
+        self.numer.div_euclid(self.denom)
+    }
+
+    /// Rounds to the nearest integer, with halves going up. This is the one
+    /// rounding rule every generated time uses.
+    pub fn round(&self) -> i128 {
+        (*self + Rational::new(1, 2)).floor()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numer as f64 / self.denom as f64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRationalError;
+
+impl fmt::Display for ParseRationalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected a decimal like 0.8 or a fraction like 4/5")
+    }
+}
+
+impl std::error::Error for ParseRationalError {}
+
+/// Reads a decimal such as `-1.25`, or a fraction such as `4/5`, exactly.
+impl FromStr for Rational {
+    type Err = ParseRationalError;
+
+    fn from_str(s: &str) -> Result<Rational, ParseRationalError> {
+        let integer = |digits: &str| -> Result<i128, ParseRationalError> {
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ParseRationalError);
+            }
+            digits.parse().map_err(|_| ParseRationalError)
+        };
+        let s = s.trim();
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let value = if let Some((numer, denom)) = unsigned.split_once('/') {
+            let denom = integer(denom)?;
+            if denom == 0 {
+                return Err(ParseRationalError);
+            }
+            Rational::new(integer(numer)?, denom)
+        } else if let Some((whole, fraction)) = unsigned.split_once('.') {
+            let scale = 10_i128
+                .checked_pow(fraction.len() as u32)
+                .ok_or(ParseRationalError)?;
+            let whole = if whole.is_empty() { 0 } else { integer(whole)? };
+            Rational::integer(whole) + Rational::new(integer(fraction)?, scale)
+        } else {
+            Rational::integer(integer(unsigned)?)
+        };
+        Ok(if negative { -value } else { value })
+    }
+}
+
+impl From<i128> for Rational {
+    fn from(value: i128) -> Rational {
+        Rational::integer(value)
+    }
+}
+
+impl Add for Rational {
+    type Output = Rational;
+
+    fn add(self, other: Rational) -> Rational {
+        Rational::new(
+            self.numer * other.denom + other.numer * self.denom,
+            self.denom * other.denom,
+        )
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+
+    fn sub(self, other: Rational) -> Rational {
+        self + -other
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        Rational {
+            numer: -self.numer,
+            denom: self.denom,
+        }
+    }
+}
+
+impl Mul for Rational {
+    type Output = Rational;
+
+    fn mul(self, other: Rational) -> Rational {
+        Rational::new(self.numer * other.numer, self.denom * other.denom)
+    }
+}
+
+impl Div for Rational {
+    type Output = Rational;
+
+    fn div(self, other: Rational) -> Rational {
+        Rational::new(self.numer * other.denom, self.denom * other.numer)
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        (self.numer * other.denom).cmp(&(other.numer * self.denom))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.denom == 1 {
+            write!(f, "{}", self.numer)
+        } else {
+            write!(f, "{}/{}", self.numer, self.denom)
+        }
+    }
+}
+
+/// Written as a string such as `4/5`, so no precision is lost on the way
+/// through JSON.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Rational {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Rational {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Rational, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
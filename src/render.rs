@@ -0,0 +1,250 @@
+use std::fmt::Write;
+
+use crate::chart::{Chart, LANES};
+use crate::drs::Step;
+use crate::error::HoldError;
+use crate::timing::Timeline;
+
+const LANE_WIDTH: f64 = 24.0;
+/// Room left of the lanes for measure numbers.
+const GUTTER: f64 = 40.0;
+const MARGIN: f64 = 12.0;
+const PIXELS_PER_SECOND: f64 = 300.0;
+const NOTE_HEIGHT: f64 = 6.0;
+/// How far up a skid's slanted edges reach from the point it happens at.
+const SKID_HEIGHT: f64 = 8.0;
+/// Position units across the pad, 4096 to a lane.
+const PAD_WIDTH: i32 = 65536;
+
+const BACKGROUND: &str = "#101014";
+const LANE_LINE: &str = "#2a2a33";
+const CENTRE_LINE: &str = "#4a4a58";
+const MEASURE_LINE: &str = "#8a8a96";
+const LEFT: &str = "#f0802c";
+const RIGHT: &str = "#2c8cf0";
+const DOWN: &str = "#c850c8";
+const JUMP: &str = "#3cc864";
+
+fn colour(kind: i32) -> &'static str {
+    match kind {
+        1 => LEFT,
+        2 => RIGHT,
+        3 => DOWN,
+        _ => JUMP,
+    }
+}
+
+/// One page of the strip: the times it covers, with later times higher up.
+struct Page {
+    start_ms: i64,
+    end_ms: i64,
+}
+
+impl Page {
+    fn height(&self) -> f64 {
+        MARGIN * 2.0 + (self.end_ms - self.start_ms) as f64 * PIXELS_PER_SECOND / 1000.0
+    }
+
+    fn y(&self, ms: i64) -> f64 {
+        MARGIN + (self.end_ms - ms) as f64 * PIXELS_PER_SECOND / 1000.0
+    }
+
+    /// Whether anything between `start_ms` and `end_ms` can be seen on the
+    /// page, margins included.
+    fn shows(&self, start_ms: i64, end_ms: i64) -> bool {
+        let margin_ms = (MARGIN / PIXELS_PER_SECOND * 1000.0).ceil() as i64;
+        end_ms >= self.start_ms - margin_ms && start_ms <= self.end_ms + margin_ms
+    }
+}
+
+fn x(position: i32) -> f64 {
+    GUTTER + position as f64 / PAD_WIDTH as f64 * LANES as f64 * LANE_WIDTH
+}
+
+fn polygon(svg: &mut String, points: &[(f64, f64)], fill: &str) {
+    let points: Vec<String> = points
+        .iter()
+        .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+        .collect();
+    writeln!(
+        svg,
+        r#"<polygon points="{}" fill="{}" fill-opacity="0.35" stroke="{}"/>"#,
+        points.join(" "),
+        fill,
+        fill
+    )
+    .unwrap();
+}
+
+fn note(svg: &mut String, page: &Page, ms: i64, (left, right): (i32, i32), fill: &str) {
+    writeln!(
+        svg,
+        r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="2" fill="{}"/>"#,
+        x(left) + 1.0,
+        page.y(ms) - NOTE_HEIGHT / 2.0,
+        x(right) - x(left) - 2.0,
+        NOTE_HEIGHT,
+        fill
+    )
+    .unwrap();
+}
+
+/// Draws a hold's body as one polygon per stretch between its points, with
+/// a slanted band wherever it skids.
+fn hold(svg: &mut String, page: &Page, step: &Step) {
+    let fill = colour(step.kind);
+    let (mut left, mut right, mut ms) = (step.pos_left, step.pos_right, step.stime_ms);
+    for point in &step.long_point {
+        let (y_from, y_to) = (page.y(ms), page.y(point.time));
+        polygon(
+            svg,
+            &[
+                (x(left), y_from),
+                (x(right), y_from),
+                (x(point.pos_right), y_to),
+                (x(point.pos_left), y_to),
+            ],
+            fill,
+        );
+        (left, right, ms) = (point.pos_left, point.pos_right, point.time);
+        if let Some((skid_left, skid_right)) = point.skid_end {
+            polygon(
+                svg,
+                &[
+                    (x(left), y_to),
+                    (x(right), y_to),
+                    (x(skid_right), y_to - SKID_HEIGHT),
+                    (x(skid_left), y_to - SKID_HEIGHT),
+                ],
+                fill,
+            );
+            (left, right) = (skid_left, skid_right);
+        }
+    }
+}
+
+/// Draws `measures.0` up to, not including, `measures.1`.
+fn page_svg(timeline: &Timeline, steps: &[Step], measures: (usize, usize)) -> String {
+    let page = Page {
+        start_ms: timeline.ms_at(measures.0, 0),
+        end_ms: timeline.ms_at(measures.1, 0),
+    };
+    let width = x(PAD_WIDTH) + MARGIN;
+    let height = page.height();
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}">"#,
+        w = width,
+        h = height
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        BACKGROUND
+    )
+    .unwrap();
+
+    for lane in 0..=LANES {
+        let position = lane as i32 * PAD_WIDTH / LANES as i32;
+        let stroke = if lane == LANES / 2 {
+            CENTRE_LINE
+        } else {
+            LANE_LINE
+        };
+        writeln!(
+            svg,
+            r#"<line x1="{x:.1}" y1="0" x2="{x:.1}" y2="{:.1}" stroke="{}"/>"#,
+            height,
+            stroke,
+            x = x(position)
+        )
+        .unwrap();
+    }
+    for measure in measures.0..=measures.1 {
+        let y = page.y(timeline.ms_at(measure, 0));
+        writeln!(
+            svg,
+            r#"<line x1="{:.1}" y1="{y:.1}" x2="{:.1}" y2="{y:.1}" stroke="{}"/>"#,
+            x(0),
+            x(PAD_WIDTH),
+            MEASURE_LINE,
+            y = y
+        )
+        .unwrap();
+        if measure < measures.1 {
+            writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" fill="{}" font-family="sans-serif" font-size="11" text-anchor="end">{}</text>"#,
+                GUTTER - 6.0,
+                y + 4.0,
+                MEASURE_LINE,
+                measure
+            )
+            .unwrap();
+        }
+    }
+
+    let visible = steps
+        .iter()
+        .filter(|step| page.shows(step.stime_ms, step.etime_ms));
+    for step in visible.clone().filter(|step| step.category == 1) {
+        hold(&mut svg, &page, step);
+    }
+    for step in visible {
+        note(
+            &mut svg,
+            &page,
+            step.stime_ms,
+            (step.pos_left, step.pos_right),
+            colour(step.kind),
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+impl Chart {
+    /// Draws the chart as a strip of SVG pages, `measures_per_page`
+    /// measures to a page, going up the page as time goes on.
+    ///
+    /// Every lane of the pad is shown, with a line at each measure. Steps
+    /// are coloured by foot, jumps and downs run across the whole pad, and
+    /// holds follow their waypoints, leaning where they skid. Notes are
+    /// placed by the times and positions the chart's DRS XML would give
+    /// them.
+    ///
+    /// Fails if a hold start, waypoint or end can't be paired up.
+    pub fn to_svg_pages(&self, measures_per_page: usize) -> Result<Vec<String>, HoldError> {
+        let timeline = Timeline::new(self);
+        let steps = self.drs_steps(&timeline)?;
+        let measure_count = self.measures.len().max(1);
+        let measures_per_page = measures_per_page.max(1);
+        Ok((0..measure_count)
+            .step_by(measures_per_page)
+            .map(|first| {
+                let last = (first + measures_per_page).min(measure_count);
+                page_svg(&timeline, &steps, (first, last))
+            })
+            .collect())
+    }
+}
+
+/// Rasterises one page from [`Chart::to_svg_pages`] into a PNG. Measure
+/// numbers are left out, since no fonts are loaded.
+#[cfg(feature = "png")]
+pub fn svg_to_png(svg: &str) -> Result<Vec<u8>, crate::error::RasterError> {
+    use crate::error::RasterError;
+    use resvg::{tiny_skia, usvg};
+
+    let tree = usvg::Tree::from_str(svg, &usvg::Options::default())
+        .map_err(|e| RasterError::Svg(e.to_string()))?;
+    let size = tree.size().to_int_size();
+    let mut pixmap =
+        tiny_skia::Pixmap::new(size.width(), size.height()).ok_or(RasterError::TooLarge)?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap
+        .encode_png()
+        .map_err(|e| RasterError::Png(e.to_string()))
+}
@@ -0,0 +1,610 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::chart::{
+    Chart, Difficulty, Measure, TimeSignature, TimedEvent, HEADER_MEASURES, MAX_MEASURES,
+    TICKS_PER_MEASURE,
+};
+use crate::error::{HoldError, ParseError, ParseErrorKind, WriteError};
+use crate::lexer::{self, column_of, Token};
+use crate::note::NoteEvent;
+use crate::rational::{gcd, Rational};
+use crate::tempo::{Bpm, TempoMap};
+
+/// A `#mmm08:` reference to a `#BPMxx:` definition, resolved once the whole
+/// header has been read.
+struct PendingTempoChange<'a> {
+    measure: usize,
+    position: Rational,
+    id: &'a str,
+    line: usize,
+    column: usize,
+}
+
+pub(crate) fn parse_number<T: std::str::FromStr>(
+    value: &str,
+    line: usize,
+    column: usize,
+) -> Result<T, ParseError> {
+    value
+        .parse::<T>()
+        .map_err(|_| ParseError::new(line, column, value, ParseErrorKind::InvalidNumber))
+}
+
+/// Parses a decimal number of seconds such as `-0.125` into whole
+/// milliseconds.
+pub(crate) fn parse_seconds_as_ms(value: &str, line: usize, column: usize) -> Result<i32, ParseError> {
+    let invalid = || ParseError::new(line, column, value, ParseErrorKind::InvalidNumber);
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<i32>().map_err(|_| invalid())?
+    };
+    let millis = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+        .parse::<i32>()
+        .map_err(|_| invalid())?;
+    let ms = whole
+        .checked_mul(1000)
+        .and_then(|ms| ms.checked_add(millis))
+        .ok_or_else(invalid)?;
+    Ok(if negative { -ms } else { ms })
+}
+
+/// Splits a `mmmcc:` channel command into its measure digits and channel.
+fn channel_command(command: &str) -> Option<(&str, &str)> {
+    let digits = command.strip_suffix(':')?;
+    if digits.len() < 5 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(digits.split_at(digits.len() - 2))
+}
+
+/// Reads the measure number of a `#mmmcc:` channel, which must fit in
+/// three digits.
+fn header_measure(digits: &str, line: usize, column: usize) -> Result<usize, ParseError> {
+    let measure = parse_number::<usize>(digits, line, column)?;
+    if measure >= HEADER_MEASURES {
+        return Err(ParseError::new(
+            line,
+            column,
+            digits,
+            ParseErrorKind::MeasureOutOfRange {
+                measure,
+                max: HEADER_MEASURES - 1,
+            },
+        ));
+    }
+    Ok(measure)
+}
+
+/// Notes read for one measure, placed once its resolution is known.
+#[derive(Default)]
+struct PendingMeasure<'a> {
+    /// Resolution given after the measure number, if any.
+    resolution: Option<usize>,
+    /// Each note with its tick, and the line, column and tick text it came
+    /// from.
+    notes: Vec<(usize, NoteEvent, usize, usize, &'a str)>,
+}
+
+/// Resolution for a measure that doesn't give one: 192, doubled until its
+/// last tick fits, which covers the usual 384- and 768-tick grids.
+fn inferred_resolution(last_tick: usize) -> usize {
+    let mut resolution = TICKS_PER_MEASURE;
+    while resolution <= last_tick {
+        match resolution.checked_mul(2) {
+            Some(doubled) => resolution = doubled,
+            None => return last_tick.saturating_add(1),
+        }
+    }
+    resolution
+}
+
+/// Splits channel data into its two-digit base-36 slots, each paired with
+/// how far through the measure it falls. `00` slots are skipped.
+pub(crate) fn channel_slots(
+    data: &str,
+    line: usize,
+    column: usize,
+) -> Result<Vec<(Rational, &str, usize)>, ParseError> {
+    if data.is_empty()
+        || !data.len().is_multiple_of(2)
+        || !data.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return Err(ParseError::new(
+            line,
+            column,
+            data,
+            ParseErrorKind::InvalidChannelData,
+        ));
+    }
+    let slot_count = data.len() / 2;
+    Ok((0..slot_count)
+        .map(|slot| {
+            (
+                Rational::new(slot as i128, slot_count as i128),
+                &data[slot * 2..slot * 2 + 2],
+                column + slot * 2,
+            )
+        })
+        .filter(|(_, value, _)| *value != "00")
+        .collect())
+}
+
+pub fn parse_ssf(input: &str) -> Result<Chart, ParseError> {
+    let mut chart = Chart::default();
+    let header = &mut chart.header;
+    let mut lines = lexer::lines(input);
+    let mut bpm_definitions = HashMap::new();
+    let mut tempo_changes = Vec::new();
+    let mut signature_changes = BTreeMap::new();
+
+    let start_line = loop {
+        let Some(line) = lines.next() else {
+            return Err(ParseError::new(
+                input.lines().count() + 1,
+                1,
+                "",
+                ParseErrorKind::UnexpectedEof,
+            ));
+        };
+        let line = line?;
+        let line_num = line.number;
+        let (command, argument) = match line.token {
+            Token::Start => break line_num,
+            Token::Command { name, argument } => (name, argument),
+            Token::Measure { .. } | Token::Tick { .. } | Token::End => {
+                let data = line.text.trim();
+                return Err(ParseError::new(
+                    line_num,
+                    column_of(line.text, data),
+                    data,
+                    ParseErrorKind::DataBeforeStart,
+                ));
+            }
+        };
+        let column = column_of(line.text, argument);
+        match command {
+            "TITLE" => header.title = Some(argument.to_string()),
+            "ARTIST" => header.artist = Some(argument.to_string()),
+            "DESIGNER" => header.designer = Some(argument.to_string()),
+            "DIFFICULTY" => {
+                header.difficulty = Some(match argument {
+                    "0" => Difficulty::Easy,
+                    "1" => Difficulty::Normal,
+                    "2" => Difficulty::Hard,
+                    _ => {
+                        return Err(ParseError::new(
+                            line_num,
+                            column,
+                            argument,
+                            ParseErrorKind::InvalidDifficulty,
+                        ))
+                    }
+                });
+            }
+            "PLAYLEVEL" => header.play_level = Some(argument.to_string()),
+            "SONGID" => header.song_id = Some(argument.to_string()),
+            "WAVE" => header.wave = Some(argument.to_string()),
+            "WAVEOFFSET" => {
+                header.wave_offset = Some(parse_seconds_as_ms(argument, line_num, column)?)
+            }
+            "JACKET" => header.jacket = Some(argument.to_string()),
+            "00008:" => header.padding_bars = Some(parse_number(argument, line_num, column)?),
+            _ => {
+                if let Some(id) = command
+                    .strip_prefix("BPM")
+                    .and_then(|id| id.strip_suffix(':'))
+                {
+                    let bpm = Bpm::parse(argument).ok_or_else(|| {
+                        ParseError::new(line_num, column, argument, ParseErrorKind::InvalidBpm)
+                    })?;
+                    bpm_definitions.insert(id.to_ascii_uppercase(), bpm);
+                } else if let Some((measure, "02")) = channel_command(command) {
+                    let measure = header_measure(measure, line_num, column_of(line.text, measure))?;
+                    let signature = TimeSignature::parse(argument).ok_or_else(|| {
+                        ParseError::new(
+                            line_num,
+                            column,
+                            argument,
+                            ParseErrorKind::InvalidMeasureLength,
+                        )
+                    })?;
+                    signature_changes.insert(measure, signature);
+                } else if let Some((measure, "08")) = channel_command(command) {
+                    let measure = header_measure(measure, line_num, column_of(line.text, measure))?;
+                    for (position, id, column) in channel_slots(argument, line_num, column)? {
+                        tempo_changes.push(PendingTempoChange {
+                            measure,
+                            position,
+                            id,
+                            line: line_num,
+                            column,
+                        });
+                    }
+                }
+            }
+        }
+    };
+
+    let initial_bpm = bpm_definitions.get("01").ok_or_else(|| {
+        ParseError::new(start_line, 1, "", ParseErrorKind::MissingInitialBpm)
+    })?;
+    chart.tempo = TempoMap::new(*initial_bpm);
+    for change in tempo_changes {
+        let bpm = bpm_definitions
+            .get(&change.id.to_ascii_uppercase())
+            .ok_or_else(|| {
+                ParseError::new(
+                    change.line,
+                    change.column,
+                    change.id,
+                    ParseErrorKind::UndefinedBpm,
+                )
+            })?;
+        chart.tempo.insert(change.measure, change.position, *bpm);
+    }
+
+    // Only measures with a number line are kept here; the ones skipped
+    // between them are filled in as empty at the end.
+    let mut measures: BTreeMap<usize, PendingMeasure> = BTreeMap::new();
+    let mut current_measure: Option<usize> = None;
+
+    for line in lines {
+        let line = line?;
+        let line_num = line.number;
+        match line.token {
+            Token::End => break,
+            Token::Tick {
+                tick: tick_string,
+                notes,
+            } => {
+                let column = column_of(line.text, tick_string);
+                let Some(measure_num) = current_measure else {
+                    let data = line.text.trim();
+                    return Err(ParseError::new(
+                        line_num,
+                        column,
+                        data,
+                        ParseErrorKind::NoteBeforeMeasure,
+                    ));
+                };
+                let tick = parse_number::<usize>(tick_string, line_num, column)?;
+                for note_string in notes.split(',').map(str::trim) {
+                    if note_string.is_empty() {
+                        continue;
+                    }
+                    let note = NoteEvent::from_string(note_string).map_err(|error| {
+                        error.offset(line_num, column_of(line.text, note_string))
+                    })?;
+                    measures
+                        .entry(measure_num)
+                        .or_default()
+                        .notes
+                        .push((tick, note, line_num, column, tick_string));
+                }
+            }
+            Token::Measure { number, resolution } => {
+                let column = column_of(line.text, number);
+                let measure_num = parse_number::<usize>(number, line_num, column)?;
+                if measure_num
+                    .checked_add(1)
+                    .is_none_or(|count| count > MAX_MEASURES)
+                {
+                    return Err(ParseError::new(
+                        line_num,
+                        column,
+                        number,
+                        ParseErrorKind::MeasureOutOfRange {
+                            measure: measure_num,
+                            max: MAX_MEASURES - 1,
+                        },
+                    ));
+                }
+                let pending = measures.entry(measure_num).or_default();
+                if let Some(resolution) = resolution {
+                    let column = column_of(line.text, resolution);
+                    let ticks = parse_number::<usize>(resolution, line_num, column)?;
+                    if ticks == 0 {
+                        return Err(ParseError::new(
+                            line_num,
+                            column,
+                            resolution,
+                            ParseErrorKind::InvalidResolution,
+                        ));
+                    }
+                    pending.resolution = Some(ticks);
+                }
+                current_measure = Some(measure_num);
+            }
+            Token::Command { .. } | Token::Start => {
+                let command = line.text.trim();
+                return Err(ParseError::new(
+                    line_num,
+                    column_of(line.text, command),
+                    command,
+                    ParseErrorKind::CommandAfterStart,
+                ));
+            }
+        }
+    }
+
+    let mut events = Vec::new();
+    let measure_count = measures.keys().next_back().map_or(0, |last| last + 1);
+    for measure_num in 0..measure_count {
+        let pending = measures.remove(&measure_num).unwrap_or_default();
+        let last_tick = pending.notes.iter().map(|(tick, ..)| *tick).max();
+        let resolution = pending
+            .resolution
+            .unwrap_or_else(|| inferred_resolution(last_tick.unwrap_or(0)));
+        for (tick, event, line_num, column, tick_string) in pending.notes {
+            if tick >= resolution {
+                return Err(ParseError::new(
+                    line_num,
+                    column,
+                    tick_string,
+                    ParseErrorKind::TickOutOfRange {
+                        tick,
+                        ticks_per_measure: resolution,
+                    },
+                ));
+            }
+            events.push(TimedEvent {
+                measure: measure_num,
+                tick,
+                event,
+            });
+        }
+        chart.measures.push(Measure::with_resolution(resolution));
+    }
+    chart.events = events.into_iter().collect();
+
+    // A measure length applies from its measure until the next one.
+    let mut signature = TimeSignature::COMMON;
+    for (measure_num, measure) in chart.measures.iter_mut().enumerate() {
+        if let Some(change) = signature_changes.get(&measure_num) {
+            signature = *change;
+        }
+        measure.signature = signature;
+    }
+
+    Ok(chart)
+}
+
+/// Formats milliseconds as the decimal seconds `WAVEOFFSET` uses.
+fn format_ms_as_seconds(ms: i32) -> String {
+    let sign = if ms < 0 { "-" } else { "" };
+    let (whole, millis) = (ms.unsigned_abs() / 1000, ms.unsigned_abs() % 1000);
+    if millis == 0 {
+        format!("{}{}", sign, whole)
+    } else {
+        let fraction = format!("{:03}", millis);
+        format!("{}{}.{}", sign, whole, fraction.trim_end_matches('0'))
+    }
+}
+
+/// Two-digit base-36 id for the `index`th `#BPMxx:` definition.
+fn bpm_id(index: usize) -> String {
+    let digit = |value: usize| {
+        char::from_digit(value as u32, 36)
+            .unwrap()
+            .to_ascii_uppercase()
+    };
+    format!("{}{}", digit(index / 36), digit(index % 36))
+}
+
+/// `event` with its hold id replaced, if it has one.
+fn with_hold_id(mut event: NoteEvent, new_id: u8) -> NoteEvent {
+    match &mut event {
+        NoteEvent::LeftHoldStart { id, .. }
+        | NoteEvent::RightHoldStart { id, .. }
+        | NoteEvent::SlideWaypoint { id, .. }
+        | NoteEvent::SlideEnd { id, .. }
+        | NoteEvent::SimpleSkidWaypoint { id, .. }
+        | NoteEvent::ComplexSkidWaypoint { id, .. }
+        | NoteEvent::SimpleSkidEnd { id, .. }
+        | NoteEvent::ComplexSkidEnd { id, .. } => *id = new_id,
+        _ => {}
+    }
+    event
+}
+
+impl Chart {
+    /// Puts the chart in the form `fmt` writes: hold ids renumbered from 0
+    /// in the order holds start, each taking the lowest id free at its
+    /// start, and every tick's notes sorted by type, then id, lane and
+    /// width.
+    ///
+    /// Fails if the holds can't be paired up, since they couldn't be
+    /// renumbered.
+    pub fn normalize(&mut self) -> Result<(), HoldError> {
+        let holds = self.holds()?;
+        // For each new id, where the hold last given it ends.
+        let mut taken: Vec<(usize, usize)> = Vec::new();
+        let mut events: Vec<TimedEvent> = self
+            .events
+            .iter()
+            .filter(|note| note.event.hold_id().is_none())
+            .copied()
+            .collect();
+        for hold in holds {
+            let start = (hold.measure, hold.tick);
+            let end = hold.end().position();
+            // An id is only free again after the tick its hold ends. Once
+            // sorted, a waypoint or end sits before a start on one tick, so
+            // a hold starting there would take over the old hold's end.
+            let id = match taken.iter().position(|taken_end| *taken_end < start) {
+                Some(id) => {
+                    taken[id] = end;
+                    id
+                }
+                None => {
+                    taken.push(end);
+                    taken.len() - 1
+                }
+            } as u8;
+            events.push(TimedEvent {
+                measure: hold.measure,
+                tick: hold.tick,
+                event: with_hold_id(hold.start, id),
+            });
+            events.extend(hold.points.iter().map(|point| TimedEvent {
+                event: with_hold_id(point.event, id),
+                ..*point
+            }));
+        }
+        self.events = events.into_iter().collect();
+        for tick in self.events.ticks_mut() {
+            tick.sort_by_key(|note| note.event);
+        }
+        Ok(())
+    }
+
+    /// Writes the chart back out as SSF that `parse_ssf` reads.
+    pub fn to_ssf(&self) -> Result<String, WriteError> {
+        let mut output = String::new();
+        let header = &self.header;
+
+        let text_fields = [
+            ("TITLE", &header.title),
+            ("ARTIST", &header.artist),
+            ("DESIGNER", &header.designer),
+        ];
+        for (command, value) in text_fields {
+            if let Some(value) = value {
+                output += &format!("#{} {}\n", command, value);
+            }
+        }
+        if let Some(difficulty) = header.difficulty {
+            let value = match difficulty {
+                Difficulty::Easy => 0,
+                Difficulty::Normal => 1,
+                Difficulty::Hard => 2,
+            };
+            output += &format!("#DIFFICULTY {}\n", value);
+        }
+        let text_fields = [
+            ("PLAYLEVEL", &header.play_level),
+            ("SONGID", &header.song_id),
+            ("WAVE", &header.wave),
+        ];
+        for (command, value) in text_fields {
+            if let Some(value) = value {
+                output += &format!("#{} {}\n", command, value);
+            }
+        }
+        if let Some(offset) = header.wave_offset {
+            output += &format!("#WAVEOFFSET {}\n", format_ms_as_seconds(offset));
+        }
+        if let Some(jacket) = &header.jacket {
+            output += &format!("#JACKET {}\n", jacket);
+        }
+
+        let mut bpms = Vec::new();
+        for change in self.tempo.changes() {
+            if !bpms.contains(&change.bpm) {
+                bpms.push(change.bpm);
+            }
+        }
+        for (index, bpm) in bpms.iter().enumerate() {
+            output += &format!("#BPM{}: {}\n", bpm_id(index + 1), bpm);
+        }
+        if let Some(padding_bars) = header.padding_bars {
+            output += &format!("#00008: {}\n", padding_bars);
+        }
+
+        let in_header = |measure: usize| {
+            if measure < HEADER_MEASURES {
+                Ok(measure)
+            } else {
+                Err(WriteError::ChangeOutOfRange {
+                    measure,
+                    max: HEADER_MEASURES - 1,
+                })
+            }
+        };
+        let mut previous_signature = TimeSignature::COMMON;
+        for (measure_num, measure) in self.measures.iter().enumerate() {
+            if measure.signature != previous_signature {
+                in_header(measure_num)?;
+                output += &format!("#{:03}02: {}\n", measure_num, measure.signature);
+                previous_signature = measure.signature;
+            }
+        }
+
+        let mut changes_by_measure = BTreeMap::<usize, Vec<(Rational, usize)>>::new();
+        for change in &self.tempo.changes()[1..] {
+            if change.measure == 0 {
+                return Err(WriteError::TempoChangeInFirstMeasure {
+                    position: change.position,
+                });
+            }
+            let index = bpms.iter().position(|bpm| *bpm == change.bpm).unwrap();
+            changes_by_measure
+                .entry(in_header(change.measure)?)
+                .or_default()
+                .push((change.position, index + 1));
+        }
+        for (measure_num, changes) in changes_by_measure {
+            // Just enough slots to put every change on one of them.
+            let slot_count = changes.iter().fold(1, |count, (position, _)| {
+                count / gcd(count, position.denom()) * position.denom()
+            }) as usize;
+            let mut slots = vec!["00".to_string(); slot_count];
+            for (position, index) in changes {
+                let slot = position * Rational::integer(slot_count as i128);
+                slots[slot.numer() as usize] = bpm_id(index);
+            }
+            output += &format!("#{:03}08: {}\n", measure_num, slots.concat());
+        }
+
+        output += "#START\n";
+
+        let measure_line = |measure_num: usize| {
+            match self.measures.get(measure_num) {
+                Some(measure) if measure.resolution != TICKS_PER_MEASURE => {
+                    format!("{} {}\n", measure_num, measure.resolution)
+                }
+                _ => format!("{}\n", measure_num),
+            }
+        };
+        let mut written_measure = None;
+        for tick in self.events.ticks() {
+            let (measure_num, tick_num) = tick[0].position();
+            if written_measure != Some(measure_num) {
+                output += &measure_line(measure_num);
+                written_measure = Some(measure_num);
+            }
+            let codes = tick
+                .iter()
+                .map(|note| {
+                    note.event.to_code().ok_or(WriteError::UnencodableNote {
+                        measure: measure_num,
+                        tick: tick_num,
+                        note: note.event,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            output += &format!("{}:{}\n", tick_num, codes.join(","));
+        }
+        // The last measure is written even when empty, so the chart keeps
+        // its length.
+        if let Some(last_measure) = self.measures.len().checked_sub(1) {
+            if written_measure.is_none_or(|measure| measure < last_measure) {
+                output += &measure_line(last_measure);
+            }
+        }
+        output += "END\n";
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt;
+
+use crate::chart::{Chart, LANES};
+use crate::error::HoldError;
+use crate::note::NoteEvent;
+use crate::timing::Timeline;
+
+/// Width of the sliding window notes per second are counted over.
+pub const NPS_WINDOW_MS: i64 = 1000;
+
+/// Characters in the heat-map bar of the busiest lane.
+const HEAT_MAP_WIDTH: usize = 40;
+
+/// Numbers for judging how hard a chart is.
+///
+/// Slide and skid counts are per waypoint or end, since one hold can mix
+/// them. Every step, jump, down and hold start is one note, which is what
+/// combo and notes per second count.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub left_steps: usize,
+    pub right_steps: usize,
+    pub jumps: usize,
+    pub downs: usize,
+    pub holds: usize,
+    pub slides: usize,
+    pub simple_skids: usize,
+    pub complex_skids: usize,
+    pub max_combo: usize,
+    /// Most notes in any [`NPS_WINDOW_MS`] window, scaled to one second.
+    pub peak_nps: f64,
+    /// Notes per second from the first note to the last, or over one window
+    /// if they are closer than that.
+    pub average_nps: f64,
+    pub hold_ms: i64,
+    /// How many notes cover each lane, including hold waypoints and ends.
+    pub lane_counts: [usize; LANES],
+}
+
+/// Counts the notes of a parsed chart and works out its note density.
+pub fn stats(chart: &Chart) -> Result<Stats, HoldError> {
+    let timeline = Timeline::new(chart);
+    let holds = chart.holds()?;
+
+    let mut stats = Stats {
+        left_steps: 0,
+        right_steps: 0,
+        jumps: 0,
+        downs: 0,
+        holds: holds.len(),
+        slides: 0,
+        simple_skids: 0,
+        complex_skids: 0,
+        max_combo: 0,
+        peak_nps: 0_f64,
+        average_nps: 0_f64,
+        hold_ms: 0,
+        lane_counts: [0; LANES],
+    };
+    let mut note_times = Vec::new();
+
+    for note in &chart.events {
+        let event = &note.event;
+        match event {
+            NoteEvent::LeftStep { .. } => stats.left_steps += 1,
+            NoteEvent::RightStep { .. } => stats.right_steps += 1,
+            NoteEvent::Jump => stats.jumps += 1,
+            NoteEvent::Down => stats.downs += 1,
+            NoteEvent::SlideWaypoint { .. } | NoteEvent::SlideEnd { .. } => stats.slides += 1,
+            NoteEvent::SimpleSkidWaypoint { .. } | NoteEvent::SimpleSkidEnd { .. } => {
+                stats.simple_skids += 1
+            }
+            NoteEvent::ComplexSkidWaypoint { .. } | NoteEvent::ComplexSkidEnd { .. } => {
+                stats.complex_skids += 1
+            }
+            NoteEvent::LeftHoldStart { .. } | NoteEvent::RightHoldStart { .. } => {}
+        }
+        if matches!(
+            event,
+            NoteEvent::LeftStep { .. }
+                | NoteEvent::RightStep { .. }
+                | NoteEvent::Jump
+                | NoteEvent::Down
+                | NoteEvent::LeftHoldStart { .. }
+                | NoteEvent::RightHoldStart { .. }
+        ) {
+            note_times.push(timeline.ms_at(note.measure, note.tick));
+        }
+        for (lane, width) in event.spans() {
+            let covered = stats.lane_counts.iter_mut().skip(lane as usize);
+            for count in covered.take(width as usize) {
+                *count += 1;
+            }
+        }
+    }
+
+    for hold in &holds {
+        let end = hold.end();
+        stats.hold_ms +=
+            timeline.ms_at(end.measure, end.tick) - timeline.ms_at(hold.measure, hold.tick);
+    }
+
+    stats.max_combo = note_times.len();
+    let window_seconds = NPS_WINDOW_MS as f64 / 1000_f64;
+    let mut window_start = 0;
+    let mut peak = 0;
+    for (index, time) in note_times.iter().enumerate() {
+        while note_times[window_start] <= time - NPS_WINDOW_MS {
+            window_start += 1;
+        }
+        peak = peak.max(index + 1 - window_start);
+    }
+    stats.peak_nps = peak as f64 / window_seconds;
+    if let (Some(first), Some(last)) = (note_times.first(), note_times.last()) {
+        let seconds = ((last - first) as f64 / 1000_f64).max(window_seconds);
+        stats.average_nps = note_times.len() as f64 / seconds;
+    }
+
+    Ok(stats)
+}
+
+impl Stats {
+    fn rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("left_steps", self.left_steps.to_string()),
+            ("right_steps", self.right_steps.to_string()),
+            ("jumps", self.jumps.to_string()),
+            ("downs", self.downs.to_string()),
+            ("holds", self.holds.to_string()),
+            ("slides", self.slides.to_string()),
+            ("simple_skids", self.simple_skids.to_string()),
+            ("complex_skids", self.complex_skids.to_string()),
+            ("max_combo", self.max_combo.to_string()),
+            ("peak_nps", format!("{:.2}", self.peak_nps)),
+            ("average_nps", format!("{:.2}", self.average_nps)),
+            ("hold_ms", self.hold_ms.to_string()),
+        ]
+    }
+
+    /// Renders the stats as one JSON object, with the lane counts as an
+    /// array from lane 0.
+    pub fn to_json(&self) -> String {
+        let mut fields: Vec<String> = self
+            .rows()
+            .into_iter()
+            .map(|(name, value)| format!("  \"{}\": {}", name, value))
+            .collect();
+        let lanes: Vec<String> = self.lane_counts.iter().map(|count| count.to_string()).collect();
+        fields.push(format!("  \"lane_counts\": [{}]", lanes.join(", ")));
+        format!("{{\n{}\n}}\n", fields.join(",\n"))
+    }
+}
+
+/// Lays the stats out as a two-column table followed by the lane heat-map.
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.rows() {
+            writeln!(f, "{:<14}{:>10}", name, value)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:<6}{:>7}", "lane", "notes")?;
+        let busiest = self.lane_counts.iter().copied().max().unwrap_or(0).max(1);
+        for (lane, count) in self.lane_counts.iter().enumerate() {
+            let bar = "#".repeat(count * HEAT_MAP_WIDTH / busiest);
+            writeln!(f, "{:<6}{:>7} {}", lane, count, bar)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use crate::chart::{
+    Chart, Difficulty, Measure, TimeSignature, TimedEvent, MAX_MEASURES, TICKS_PER_MEASURE,
+};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::lexer::{column_of, split_command, strip_bom};
+use crate::note::NoteEvent;
+use crate::rational::{gcd, Rational};
+use crate::ssf::{channel_slots, parse_number, parse_seconds_as_ms};
+use crate::tempo::Bpm;
+
+/// Hold ids handed out to imported holds and slides. Each is one base-36
+/// digit, so the chart can still be written as SSF.
+const HOLD_IDS: usize = 36;
+
+/// Something in a SUS chart with no DRS equivalent, reported by
+/// [`parse_sus`] and then left out or simplified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SusWarning {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SusWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Warnings so far, at most one of each kind per line.
+#[derive(Default)]
+struct Warnings {
+    warnings: Vec<SusWarning>,
+    seen: HashSet<(usize, &'static str)>,
+}
+
+impl Warnings {
+    fn warn(&mut self, line: usize, message: &'static str) {
+        if self.seen.insert((line, message)) {
+            self.warnings.push(SusWarning {
+                line,
+                message: message.to_string(),
+            });
+        }
+    }
+}
+
+/// A measure number and how far through that measure, from 0 up to 1.
+type Position = (usize, Rational);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    // Ends sort first, so a channel can end one hold and start the next on
+    // the same tick.
+    End,
+    Start,
+    Relay,
+}
+
+/// One point of a hold or slide channel.
+struct ChainPoint {
+    position: Position,
+    role: Role,
+    lane: u8,
+    width: u8,
+    line: usize,
+}
+
+/// A `#BPM` reference from a `#mmm08:` channel, resolved once every
+/// definition has been read.
+struct PendingTempoChange<'a> {
+    position: Position,
+    id: &'a str,
+    line: usize,
+    column: usize,
+}
+
+/// Strips the double quotes SUS puts around text arguments.
+fn unquote(argument: &str) -> &str {
+    argument
+        .strip_prefix('"')
+        .and_then(|argument| argument.strip_suffix('"'))
+        .unwrap_or(argument)
+}
+
+/// Splits a `mmmcx:` or `mmmcxy:` channel command into its measure number
+/// and the channel characters after it.
+fn channel_command(command: &str) -> Option<(usize, &[u8])> {
+    let name = command.strip_suffix(':')?;
+    if !matches!(name.len(), 5 | 6) || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some((name[..3].parse().ok()?, &name.as_bytes()[3..]))
+}
+
+fn base36(c: u8) -> Option<u8> {
+    (c as char).to_digit(36).map(|digit| digit as u8)
+}
+
+/// A SUS BPM, which may have more decimal places than DRS keeps, rounded
+/// to hundredths.
+fn parse_bpm(value: &str) -> Option<Bpm> {
+    let bpm = (value.parse::<Rational>().ok()? * Rational::integer(100)).round();
+    u32::try_from(bpm).ok().filter(|bpm| *bpm > 0).map(Bpm)
+}
+
+/// Whether a note centred at or left of the middle of the pad goes to the
+/// left foot. SUS has no feet, so this is the closest guess.
+fn is_left(lane: u8, width: u8) -> bool {
+    lane as usize * 2 + width as usize <= crate::chart::LANES
+}
+
+/// Reads a SUS (Sliding Universal Score) chart.
+///
+/// Taps become steps and holds and slides become DRS holds, with relay
+/// points as waypoints. Feet go by which half of the pad a note starts on.
+/// Anything DRS can't play, such as air notes, damage notes, notes on lanes
+/// past 15 and speed changes, is left out, and critical taps and flicks
+/// become plain steps; each case comes back as a [`SusWarning`].
+pub fn parse_sus(input: &str) -> Result<(Chart, Vec<SusWarning>), ParseError> {
+    let mut chart = Chart::default();
+    let header = &mut chart.header;
+    let mut warnings = Warnings::default();
+    let mut measure_base: usize = 0;
+    let mut bpm_definitions = HashMap::new();
+    let mut tempo_changes = Vec::new();
+    let mut signature_changes = BTreeMap::new();
+    let mut notes: Vec<(Position, NoteEvent)> = Vec::new();
+    let mut channels: BTreeMap<(u8, u8), Vec<ChainPoint>> = BTreeMap::new();
+
+    for (index, text) in strip_bom(input).lines().enumerate() {
+        let line_num = index + 1;
+        // Only `#` lines mean anything; SUS treats the rest as comments.
+        let Some(rest) = text.trim().strip_prefix('#') else {
+            continue;
+        };
+        let (name, argument) = split_command(rest);
+        let column = column_of(text, argument);
+        let name = name.to_ascii_uppercase();
+        let text_value = || Some(unquote(argument).to_string());
+        match name.as_str() {
+            "TITLE" => header.title = text_value(),
+            "ARTIST" => header.artist = text_value(),
+            "DESIGNER" => header.designer = text_value(),
+            "PLAYLEVEL" => header.play_level = text_value(),
+            "SONGID" => header.song_id = text_value(),
+            "WAVE" => header.wave = text_value(),
+            "JACKET" => header.jacket = text_value(),
+            "DIFFICULTY" => match unquote(argument) {
+                "0" => header.difficulty = Some(Difficulty::Easy),
+                "1" => header.difficulty = Some(Difficulty::Normal),
+                "2" => header.difficulty = Some(Difficulty::Hard),
+                _ => warnings.warn(line_num, "only difficulties 0 to 2 have a DRS equivalent"),
+            },
+            "WAVEOFFSET" => {
+                header.wave_offset = Some(parse_seconds_as_ms(argument, line_num, column)?)
+            }
+            "MEASUREBS" => measure_base = parse_number(argument, line_num, column)?,
+            "HISPEED" | "MEASUREHS" | "NOSPEED" => {
+                warnings.warn(line_num, "speed changes have no DRS equivalent")
+            }
+            _ if name.starts_with("TIL") => {
+                warnings.warn(line_num, "speed changes have no DRS equivalent")
+            }
+            _ if name.starts_with("BPM") && name.ends_with(':') => {
+                let bpm = parse_bpm(argument).ok_or_else(|| {
+                    ParseError::new(line_num, column, argument, ParseErrorKind::InvalidBpm)
+                })?;
+                bpm_definitions.insert(name[3..name.len() - 1].to_string(), bpm);
+            }
+            _ => {
+                let Some((measure, channel)) = channel_command(&name) else {
+                    continue;
+                };
+                let measure = measure_base
+                    .checked_add(measure)
+                    .filter(|measure| *measure < MAX_MEASURES)
+                    .ok_or_else(|| {
+                        ParseError::new(
+                            line_num,
+                            column_of(text, rest),
+                            &rest[..3],
+                            ParseErrorKind::MeasureOutOfRange {
+                                measure: measure_base.saturating_add(measure),
+                                max: MAX_MEASURES - 1,
+                            },
+                        )
+                    })?;
+                match *channel {
+                    [b'0', b'2'] => {
+                        let signature = TimeSignature::parse(argument).ok_or_else(|| {
+                            ParseError::new(
+                                line_num,
+                                column,
+                                argument,
+                                ParseErrorKind::InvalidMeasureLength,
+                            )
+                        })?;
+                        signature_changes.insert(measure, signature);
+                        continue;
+                    }
+                    [b'0', b'8'] => {
+                        for (position, id, column) in channel_slots(argument, line_num, column)? {
+                            tempo_changes.push(PendingTempoChange {
+                                position: (measure, position),
+                                id,
+                                line: line_num,
+                                column,
+                            });
+                        }
+                        continue;
+                    }
+                    [b'1' | b'5', _] | [b'2' | b'3' | b'4' | b'8', _, _] => {}
+                    _ => {
+                        warnings.warn(line_num, "unknown channel ignored");
+                        continue;
+                    }
+                }
+
+                let lane = base36(channel[1]).unwrap();
+                for (position, value, column) in channel_slots(argument, line_num, column)? {
+                    if lane as usize >= crate::chart::LANES {
+                        warnings.warn(line_num, "lanes past 15 have no DRS equivalent");
+                        continue;
+                    }
+                    let position = (measure, position);
+                    let value = value.as_bytes();
+                    let kind = base36(value[0]).unwrap();
+                    let width = base36(value[1]).filter(|width| *width > 0).ok_or_else(|| {
+                        ParseError::new(
+                            line_num,
+                            column + 1,
+                            &(value[1] as char).to_string(),
+                            ParseErrorKind::InvalidDigit("width"),
+                        )
+                    })?;
+                    let step = if is_left(lane, width) {
+                        NoteEvent::LeftStep { lane, width }
+                    } else {
+                        NoteEvent::RightStep { lane, width }
+                    };
+                    match (channel, kind) {
+                        ([b'1', _], 1) => notes.push((position, step)),
+                        ([b'1', _], 2) => {
+                            warnings.warn(line_num, "critical taps are imported as plain steps");
+                            notes.push((position, step));
+                        }
+                        ([b'1', _], 3) => {
+                            warnings.warn(line_num, "flicks are imported as plain steps");
+                            notes.push((position, step));
+                        }
+                        ([b'1', _], 4) => {
+                            warnings.warn(line_num, "damage notes have no DRS equivalent")
+                        }
+                        ([b'5', _], _) => {
+                            warnings.warn(line_num, "air notes have no DRS equivalent")
+                        }
+                        ([b'8', _, _], _) => {
+                            warnings.warn(line_num, "air holds have no DRS equivalent")
+                        }
+                        ([b'3' | b'4', _, _], 4) => {
+                            warnings.warn(line_num, "slide curves are imported as straight lines")
+                        }
+                        ([c @ (b'2' | b'3' | b'4'), _, id], 1 | 2 | 3 | 5) => {
+                            let role = match kind {
+                                1 => Role::Start,
+                                2 => Role::End,
+                                _ => Role::Relay,
+                            };
+                            channels
+                                .entry((*c, id.to_ascii_uppercase()))
+                                .or_default()
+                                .push(ChainPoint {
+                                    position,
+                                    role,
+                                    lane,
+                                    width,
+                                    line: line_num,
+                                });
+                        }
+                        _ => warnings.warn(line_num, "unknown note type ignored"),
+                    }
+                }
+            }
+        }
+    }
+
+    // Without a tempo at the very start, the chart keeps the default one
+    // until its first change.
+    if !tempo_changes
+        .iter()
+        .any(|change| change.position == (0, Rational::ZERO))
+    {
+        let line = tempo_changes.first().map_or(1, |change| change.line);
+        warnings.warn(line, "no BPM at the start of measure 0, so the chart starts at 120");
+    }
+    for change in tempo_changes {
+        let bpm = bpm_definitions
+            .get(&change.id.to_ascii_uppercase())
+            .ok_or_else(|| {
+                ParseError::new(
+                    change.line,
+                    change.column,
+                    change.id,
+                    ParseErrorKind::UndefinedBpm,
+                )
+            })?;
+        let (measure, position) = change.position;
+        chart.tempo.insert(measure, position, *bpm);
+    }
+
+    // Pair each channel's starts, relay points and ends into chains.
+    let mut chains = Vec::new();
+    for mut points in channels.into_values() {
+        points.sort_by_key(|point| (point.position, point.role));
+        let mut open: Option<Vec<ChainPoint>> = None;
+        for point in points {
+            match point.role {
+                Role::Start => {
+                    if let Some(unfinished) = open.replace(vec![point]) {
+                        warnings.warn(unfinished[0].line, "hold or slide never ends");
+                    }
+                }
+                Role::Relay => match &mut open {
+                    Some(chain) => chain.push(point),
+                    None => warnings.warn(point.line, "relay point outside a hold or slide"),
+                },
+                Role::End => match open.take() {
+                    Some(mut chain) => {
+                        chain.push(point);
+                        chains.push(chain);
+                    }
+                    None => warnings.warn(point.line, "hold or slide end without a start"),
+                },
+            }
+        }
+        if let Some(unfinished) = open {
+            warnings.warn(unfinished[0].line, "hold or slide never ends");
+        }
+    }
+
+    // Hand out ids in start order, reusing each once its chain has ended.
+    chains.sort_by_key(|chain| chain[0].position);
+    let mut busy_until: Vec<Option<Position>> = vec![None; HOLD_IDS];
+    for chain in chains {
+        let (start, end) = (&chain[0], &chain[chain.len() - 1]);
+        let Some(id) = busy_until
+            .iter()
+            .position(|until| until.is_none_or(|until| until <= start.position))
+        else {
+            warnings.warn(start.line, "more than 36 holds at once");
+            continue;
+        };
+        busy_until[id] = Some(end.position);
+        let id = id as u8;
+        let (lane, width) = (start.lane, start.width);
+        notes.push((
+            start.position,
+            if is_left(lane, width) {
+                NoteEvent::LeftHoldStart { id, lane, width }
+            } else {
+                NoteEvent::RightHoldStart { id, lane, width }
+            },
+        ));
+        for (index, point) in chain.iter().enumerate().skip(1) {
+            let (lane, width) = (point.lane, point.width);
+            notes.push((
+                point.position,
+                if index == chain.len() - 1 {
+                    NoteEvent::SlideEnd { id, lane, width }
+                } else {
+                    NoteEvent::SlideWaypoint { id, lane, width }
+                },
+            ));
+        }
+    }
+
+    // Each measure keeps the usual grid unless a note falls between its
+    // ticks, in which case it gets the coarsest finer grid they all land on.
+    let measure_count = notes
+        .iter()
+        .map(|((measure, _), _)| *measure)
+        .chain(signature_changes.keys().copied())
+        .chain(chart.tempo.changes().iter().map(|change| change.measure))
+        .max()
+        .map_or(0, |last| last + 1);
+    let mut resolutions = vec![TICKS_PER_MEASURE as i128; measure_count];
+    for ((measure, position), _) in &notes {
+        let resolution = &mut resolutions[*measure];
+        *resolution = *resolution / gcd(*resolution, position.denom()) * position.denom();
+    }
+    let mut signature = TimeSignature::COMMON;
+    for (measure_num, resolution) in resolutions.iter().enumerate() {
+        if let Some(change) = signature_changes.get(&measure_num) {
+            signature = *change;
+        }
+        let mut measure = Measure::with_resolution(*resolution as usize);
+        measure.signature = signature;
+        chart.measures.push(measure);
+    }
+    chart.events = notes
+        .into_iter()
+        .map(|((measure, position), event)| TimedEvent {
+            measure,
+            tick: (position * Rational::integer(chart.measures[measure].resolution as i128))
+                .numer() as usize,
+            event,
+        })
+        .collect();
+
+    let mut warnings = warnings.warnings;
+    warnings.sort_by_key(|warning| warning.line);
+    Ok((chart, warnings))
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+
+use crate::rational::Rational;
+
+/// A tempo in hundredths of a beat per minute, the unit `bpm_info` uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bpm(pub u32);
+
+impl Bpm {
+    /// Parses a decimal BPM such as `150` or `172.5`, keeping up to two
+    /// fractional digits exactly.
+    pub fn parse(value: &str) -> Option<Bpm> {
+        let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+        if whole.is_empty()
+            || fraction.len() > 2
+            || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        let whole = whole.parse::<u32>().ok()?;
+        let fraction = format!("{:0<2}", fraction).parse::<u32>().ok()?;
+        let bpm = whole.checked_mul(100)?.checked_add(fraction)?;
+        (bpm > 0).then_some(Bpm(bpm))
+    }
+}
+
+impl fmt::Display for Bpm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 % 100 {
+            0 => write!(f, "{}", self.0 / 100),
+            fraction if fraction % 10 == 0 => write!(f, "{}.{}", self.0 / 100, fraction / 10),
+            fraction => write!(f, "{}.{:02}", self.0 / 100, fraction),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TempoChange {
+    pub measure: usize,
+    /// How far through the measure the change is, from 0 up to 1.
+    pub position: Rational,
+    pub bpm: Bpm,
+}
+
+impl TempoChange {
+    fn key(&self) -> (usize, Rational) {
+        (self.measure, self.position)
+    }
+}
+
+/// Every tempo in a chart, ordered by position. The first change is always
+/// at the very start of the chart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TempoMap {
+    changes: Vec<TempoChange>,
+}
+
+impl TempoMap {
+    pub fn new(initial: Bpm) -> TempoMap {
+        TempoMap {
+            changes: vec![TempoChange {
+                measure: 0,
+                position: Rational::ZERO,
+                bpm: initial,
+            }],
+        }
+    }
+
+    /// Sets the tempo from `position` through `measure` onwards, replacing
+    /// any change already there.
+    pub fn insert(&mut self, measure: usize, position: Rational, bpm: Bpm) {
+        let change = TempoChange {
+            measure,
+            position,
+            bpm,
+        };
+        match self
+            .changes
+            .binary_search_by_key(&change.key(), TempoChange::key)
+        {
+            Ok(index) => self.changes[index] = change,
+            Err(index) => self.changes.insert(index, change),
+        }
+    }
+
+    pub fn changes(&self) -> &[TempoChange] {
+        &self.changes
+    }
+
+    pub fn initial_bpm(&self) -> Bpm {
+        self.changes[0].bpm
+    }
+
+    pub fn min_bpm(&self) -> Bpm {
+        self.changes.iter().map(|change| change.bpm).min().unwrap()
+    }
+
+    pub fn max_bpm(&self) -> Bpm {
+        self.changes.iter().map(|change| change.bpm).max().unwrap()
+    }
+}
+
+impl Default for TempoMap {
+    fn default() -> Self {
+        TempoMap::new(Bpm(12000))
+    }
+}
+
+/// Written as the list of changes.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TempoMap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.changes.serialize(serializer)
+    }
+}
+
+/// Read from a list of changes, the first of which has to be at the start
+/// of measure 0.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TempoMap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<TempoMap, D::Error> {
+        use serde::de::Error;
+
+        let changes = Vec::<TempoChange>::deserialize(deserializer)?;
+        for change in &changes {
+            if change.bpm.0 == 0 {
+                return Err(D::Error::custom("tempo must be above 0"));
+            }
+            if change.position < Rational::ZERO || change.position >= Rational::integer(1) {
+                return Err(D::Error::custom(
+                    "tempo change position must be from 0 up to 1",
+                ));
+            }
+        }
+        let Some(first) = changes.first() else {
+            return Err(D::Error::custom("a tempo map needs at least one tempo"));
+        };
+        if first.key() != (0, Rational::ZERO) {
+            return Err(D::Error::custom(
+                "the first tempo change must be at the start of measure 0",
+            ));
+        }
+        let mut tempo = TempoMap::new(first.bpm);
+        for change in &changes[1..] {
+            tempo.insert(change.measure, change.position, change.bpm);
+        }
+        Ok(tempo)
+    }
+}
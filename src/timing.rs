@@ -0,0 +1,180 @@
+use crate::chart::{Chart, TimeSignature, TICKS_PER_MEASURE};
+use crate::rational::Rational;
+use crate::tempo::Bpm;
+
+struct TempoSegment {
+    beat: Rational,
+    ms: Rational,
+    bpm: Bpm,
+}
+
+/// Milliseconds per beat at `bpm`: 60000 ms per minute, with the tempo in
+/// hundredths.
+fn ms_per_beat(bpm: Bpm) -> Rational {
+    Rational::new(6000000, bpm.0 as i128)
+}
+
+impl TempoSegment {
+    fn ms_at(&self, beat: Rational) -> Rational {
+        self.ms + (beat - self.beat) * ms_per_beat(self.bpm)
+    }
+}
+
+/// Converts measure/tick positions in one chart into milliseconds and
+/// delta-time, following its time signatures and tempo map.
+///
+/// Time 0 is the start of the audio. The chart's first measure starts after
+/// its padding bars plus its `WAVEOFFSET`, both played at the starting tempo.
+///
+/// Positions are tracked as exact fractions of a beat, so nothing drifts no
+/// matter how long the chart is. The only rounding happens once, when an
+/// exact time is turned into whole milliseconds or delta-time, and it always
+/// rounds to the nearest integer with halves going up.
+pub struct Timeline {
+    /// Beat at which each measure starts, plus one entry for the end of the
+    /// last measure.
+    measure_starts: Vec<Rational>,
+    /// Ticks in each measure; positions past the last measure use
+    /// [`TICKS_PER_MEASURE`].
+    resolutions: Vec<usize>,
+    /// Signature assumed for positions past the last measure.
+    trailing_signature: TimeSignature,
+    segments: Vec<TempoSegment>,
+}
+
+impl Timeline {
+    pub fn new(chart: &Chart) -> Timeline {
+        let initial_bpm = chart.tempo.initial_bpm();
+        let first_signature = chart
+            .measures
+            .first()
+            .map_or(TimeSignature::COMMON, |measure| measure.signature);
+        let padding =
+            Rational::integer(chart.header.padding_bars.unwrap_or(0) as i128) * first_signature.beats();
+        let offset = Rational::integer(chart.header.wave_offset.unwrap_or(0) as i128)
+            / ms_per_beat(initial_bpm);
+
+        let mut measure_starts = vec![padding + offset];
+        for measure in &chart.measures {
+            measure_starts.push(*measure_starts.last().unwrap() + measure.signature.beats());
+        }
+        let mut timeline = Timeline {
+            measure_starts,
+            resolutions: chart.measures.iter().map(|measure| measure.resolution).collect(),
+            trailing_signature: chart
+                .measures
+                .last()
+                .map_or(TimeSignature::COMMON, |measure| measure.signature),
+            segments: vec![TempoSegment {
+                beat: Rational::ZERO,
+                ms: Rational::ZERO,
+                bpm: initial_bpm,
+            }],
+        };
+        for change in &chart.tempo.changes()[1..] {
+            let beat = timeline.beat_at_position(change.measure, change.position);
+            let ms = timeline.segments.last().unwrap().ms_at(beat);
+            timeline.segments.push(TempoSegment {
+                beat,
+                ms,
+                bpm: change.bpm,
+            });
+        }
+        timeline
+    }
+
+    /// Quarter-note beats from time 0 to `measure`/`tick`.
+    pub fn beat_at(&self, measure: usize, tick: usize) -> Rational {
+        let resolution = self
+            .resolutions
+            .get(measure)
+            .copied()
+            .unwrap_or(TICKS_PER_MEASURE);
+        self.beat_at_position(measure, Rational::new(tick as i128, resolution as i128))
+    }
+
+    /// Quarter-note beats from time 0 to `position` (0 up to 1) through
+    /// `measure`.
+    pub fn beat_at_position(&self, measure: usize, position: Rational) -> Rational {
+        let last = self.measure_starts.len() - 1;
+        let (start, length) = if measure < last {
+            let start = self.measure_starts[measure];
+            (start, self.measure_starts[measure + 1] - start)
+        } else {
+            let length = self.trailing_signature.beats();
+            (
+                self.measure_starts[last] + Rational::integer((measure - last) as i128) * length,
+                length,
+            )
+        };
+        start + length * position
+    }
+
+    /// The measure `beat` falls in and how far through it. Beats before the
+    /// first measure give measure 0 and a negative position.
+    pub(crate) fn position_at_beat(&self, beat: Rational) -> (usize, Rational) {
+        let last = self.measure_starts.len() - 1;
+        if beat >= self.measure_starts[last] {
+            let past = (beat - self.measure_starts[last]) / self.trailing_signature.beats();
+            let whole = past.floor();
+            let measure = usize::try_from(whole).unwrap_or(usize::MAX);
+            return (last.saturating_add(measure), past - Rational::integer(whole));
+        }
+        let measure = self
+            .measure_starts
+            .partition_point(|start| *start <= beat)
+            .saturating_sub(1);
+        let start = self.measure_starts[measure];
+        (measure, (beat - start) / (self.measure_starts[measure + 1] - start))
+    }
+
+    /// Exact milliseconds from time 0 to `measure`/`tick`, integrating
+    /// across every tempo change before it.
+    pub fn exact_ms_at(&self, measure: usize, tick: usize) -> Rational {
+        self.ms_at_beat(self.beat_at(measure, tick))
+    }
+
+    /// Exact beat `ms` milliseconds from time 0, the inverse of
+    /// [`Timeline::exact_ms_at`].
+    pub(crate) fn beat_at_ms(&self, ms: Rational) -> Rational {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|segment| segment.ms <= ms)
+            .unwrap_or(&self.segments[0]);
+        segment.beat + (ms - segment.ms) / ms_per_beat(segment.bpm)
+    }
+
+    fn ms_at_beat(&self, beat: Rational) -> Rational {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|segment| segment.beat <= beat)
+            .unwrap_or(&self.segments[0]);
+        segment.ms_at(beat)
+    }
+
+    /// Milliseconds from time 0 to `measure`/`tick`, rounded to the nearest
+    /// millisecond.
+    pub fn ms_at(&self, measure: usize, tick: usize) -> i64 {
+        self.exact_ms_at(measure, tick).round() as i64
+    }
+
+    /// Delta-time (480 per beat) from time 0 to `measure`/`tick`, rounded
+    /// to the nearest unit.
+    pub fn dt_at(&self, measure: usize, tick: usize) -> i64 {
+        (self.beat_at(measure, tick) * Rational::integer(480)).round() as i64
+    }
+
+    /// [`Timeline::ms_at`] for a position given as a fraction of `measure`.
+    pub fn ms_at_position(&self, measure: usize, position: Rational) -> i64 {
+        self.ms_at_beat(self.beat_at_position(measure, position)).round() as i64
+    }
+
+    /// [`Timeline::dt_at`] for a position given as a fraction of `measure`.
+    pub fn dt_at_position(&self, measure: usize, position: Rational) -> i64 {
+        (self.beat_at_position(measure, position) * Rational::integer(480)).round() as i64
+    }
+}
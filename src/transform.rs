@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use crate::chart::{Chart, LANES};
+use crate::error::TransformError;
+use crate::note::NoteEvent;
+use crate::rational::Rational;
+use crate::tempo::{Bpm, TempoMap};
+
+/// Returns the note with every lane span moved to the lane `f` picks for
+/// it, or `None` if `f` has no lane for one of them.
+fn move_spans(event: &NoteEvent, f: impl Fn(u8, u8) -> Option<u8>) -> Option<NoteEvent> {
+    let mut event = *event;
+    match &mut event {
+        NoteEvent::Jump | NoteEvent::Down => {}
+        NoteEvent::LeftStep { lane, width }
+        | NoteEvent::RightStep { lane, width }
+        | NoteEvent::LeftHoldStart { lane, width, .. }
+        | NoteEvent::RightHoldStart { lane, width, .. }
+        | NoteEvent::SlideWaypoint { lane, width, .. }
+        | NoteEvent::SlideEnd { lane, width, .. }
+        | NoteEvent::SimpleSkidWaypoint { lane, width, .. }
+        | NoteEvent::SimpleSkidEnd { lane, width, .. } => *lane = f(*lane, *width)?,
+        NoteEvent::ComplexSkidWaypoint {
+            lane_start,
+            width_start,
+            lane_end,
+            width_end,
+            ..
+        }
+        | NoteEvent::ComplexSkidEnd {
+            lane_start,
+            width_start,
+            lane_end,
+            width_end,
+            ..
+        } => {
+            *lane_start = f(*lane_start, *width_start)?;
+            *lane_end = f(*lane_end, *width_end)?;
+        }
+    }
+    Some(event)
+}
+
+/// Moves a span by `offset` lanes if it stays on the pad.
+fn offset_lane(lane: u8, width: u8, offset: i32) -> Option<u8> {
+    let lane = lane as i32 + offset;
+    (lane >= 0 && lane + width as i32 <= LANES as i32).then_some(lane as u8)
+}
+
+fn swap_foot(event: NoteEvent) -> NoteEvent {
+    match event {
+        NoteEvent::LeftStep { lane, width } => NoteEvent::RightStep { lane, width },
+        NoteEvent::RightStep { lane, width } => NoteEvent::LeftStep { lane, width },
+        NoteEvent::LeftHoldStart { id, lane, width } => NoteEvent::RightHoldStart { id, lane, width },
+        NoteEvent::RightHoldStart { id, lane, width } => NoteEvent::LeftHoldStart { id, lane, width },
+        other => other,
+    }
+}
+
+fn is_left(event: &NoteEvent) -> bool {
+    matches!(event, NoteEvent::LeftStep { .. } | NoteEvent::LeftHoldStart { .. })
+}
+
+/// A small seeded generator (SplitMix64), so the same seed always gives the
+/// same chart.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    fn pick<T: Copy>(&mut self, choices: &[T]) -> Option<T> {
+        if choices.is_empty() {
+            return None;
+        }
+        Some(choices[(self.next() % choices.len() as u64) as usize])
+    }
+}
+
+impl Chart {
+    /// Rewrites every note in place, failing with the first one `f` can't
+    /// move.
+    fn map_notes(
+        &mut self,
+        mut f: impl FnMut(usize, usize, &NoteEvent) -> Option<NoteEvent>,
+    ) -> Result<(), TransformError> {
+        for tick in self.events.ticks_mut() {
+            for note in tick.iter_mut() {
+                note.event = f(note.measure, note.tick, &note.event).ok_or(
+                    TransformError::LaneOutOfRange {
+                        measure: note.measure,
+                        tick: note.tick,
+                        note: note.event,
+                    },
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flips the chart left to right, which also swaps every note to the
+    /// other foot.
+    pub fn mirror(&mut self) -> Result<(), TransformError> {
+        self.map_notes(|_, _, event| {
+            let mirrored = move_spans(event, |lane, width| {
+                (LANES as u8).checked_sub(lane)?.checked_sub(width)
+            })?;
+            Some(swap_foot(mirrored))
+        })
+    }
+
+    /// Moves every note `lanes` lanes to the right, or to the left when
+    /// negative.
+    pub fn shift_lanes(&mut self, lanes: i32) -> Result<(), TransformError> {
+        self.map_notes(|_, _, event| move_spans(event, |lane, width| offset_lane(lane, width, lanes)))
+    }
+
+    /// Moves notes to random lanes picked from `seed`.
+    ///
+    /// Each hold moves as one piece, so its waypoints keep their shape.
+    /// Steps avoid lanes already taken on their tick by the same foot where
+    /// they can.
+    pub fn randomize_lanes(&mut self, seed: u64) -> Result<(), TransformError> {
+        let mut rng = Rng(seed);
+
+        let mut hold_offsets = HashMap::new();
+        for hold in self.holds()? {
+            let events: Vec<_> = std::iter::once((hold.measure, hold.tick, hold.start))
+                .chain(hold.points.iter().map(|point| (point.measure, point.tick, point.event)))
+                .collect();
+            let offsets: Vec<i32> = (-(LANES as i32)..=LANES as i32)
+                .filter(|offset| {
+                    events.iter().all(|(_, _, event)| {
+                        move_spans(event, |lane, width| offset_lane(lane, width, *offset)).is_some()
+                    })
+                })
+                .collect();
+            let offset = rng.pick(&offsets).unwrap_or(0);
+            for event in events {
+                hold_offsets.insert(event, offset);
+            }
+        }
+
+        for tick in self.events.ticks_mut() {
+            let mut taken = Vec::new();
+            for note in tick.iter_mut() {
+                if let Some(offset) = hold_offsets.get(&(note.measure, note.tick, note.event)) {
+                    note.event =
+                        move_spans(&note.event, |lane, width| offset_lane(lane, width, *offset))
+                            .unwrap();
+                }
+                let event = &note.event;
+                if matches!(event, NoteEvent::LeftHoldStart { .. } | NoteEvent::RightHoldStart { .. })
+                {
+                    taken.push((is_left(event), event.spans()[0]));
+                }
+            }
+
+            for note in tick.iter_mut() {
+                let (NoteEvent::LeftStep { width, .. } | NoteEvent::RightStep { width, .. }) =
+                    note.event
+                else {
+                    continue;
+                };
+                let left = is_left(&note.event);
+                let free: Vec<u8> = (0..=(LANES as u8).saturating_sub(width))
+                    .filter(|lane| {
+                        taken.iter().all(|(taken_left, (taken_lane, taken_width))| {
+                            *taken_left != left
+                                || lane + width <= *taken_lane
+                                || taken_lane + taken_width <= *lane
+                        })
+                    })
+                    .collect();
+                if let Some(lane) = rng.pick(&free) {
+                    note.event = move_spans(&note.event, |_, _| Some(lane)).unwrap();
+                }
+                taken.push((left, note.event.spans()[0]));
+            }
+        }
+        Ok(())
+    }
+
+    /// Plays the chart `rate` times as fast by scaling every tempo, so it
+    /// lines up with its audio sped up or slowed down by the same factor.
+    ///
+    /// Note, hold, tempo and measure times all follow from the tempo map, so
+    /// they scale together. `WAVEOFFSET` scales with the audio, rounded to
+    /// the millisecond. Tempos are kept in hundredths of a BPM, so a rate
+    /// that would need a finer tempo is refused rather than rounded, which
+    /// would leave the chart at a slightly different speed to the audio.
+    pub fn scale_rate(&mut self, rate: Rational) -> Result<(), TransformError> {
+        if rate <= Rational::ZERO {
+            return Err(TransformError::InvalidRate(rate));
+        }
+        let mut tempo: Option<TempoMap> = None;
+        for change in self.tempo.changes() {
+            let out_of_range = TransformError::BpmOutOfRange {
+                measure: change.measure,
+                position: change.position,
+            };
+            let bpm = Rational::integer(change.bpm.0 as i128) * rate;
+            if bpm.denom() != 1 {
+                return Err(TransformError::InexactRate {
+                    rate,
+                    bpm: change.bpm,
+                });
+            }
+            let bpm = u32::try_from(bpm.numer())
+                .ok()
+                .filter(|bpm| *bpm > 0)
+                .map(Bpm)
+                .ok_or(out_of_range)?;
+            match &mut tempo {
+                Some(tempo) => tempo.insert(change.measure, change.position, bpm),
+                None => tempo = Some(TempoMap::new(bpm)),
+            }
+        }
+        self.tempo = tempo.unwrap();
+        if let Some(offset) = self.header.wave_offset {
+            let offset = (Rational::integer(offset as i128) / rate).round();
+            self.header.wave_offset =
+                Some(i32::try_from(offset).map_err(|_| TransformError::OffsetOutOfRange)?);
+        }
+        Ok(())
+    }
+}
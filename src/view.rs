@@ -0,0 +1,228 @@
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::chart::{Chart, TimedEvent, LANES, TICKS_PER_MEASURE};
+use crate::drs::Step;
+use crate::error::HoldError;
+use crate::note::NoteEvent;
+use crate::timing::Timeline;
+
+/// Characters a [`GridView`] draws with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridStyle {
+    /// Box-drawing characters and arrows.
+    Unicode,
+    /// Plain ASCII, for terminals and fonts without the above.
+    Ascii,
+}
+
+struct Glyphs {
+    left: char,
+    right: char,
+    jump: char,
+    down: char,
+    hold: char,
+    slide: char,
+    skid: char,
+    skid_travel: char,
+    wall: char,
+    rule: char,
+    top: [char; 2],
+    separator: [char; 2],
+    bottom: [char; 2],
+}
+
+const UNICODE: Glyphs = Glyphs {
+    left: '◀',
+    right: '▶',
+    jump: '▲',
+    down: '▼',
+    hold: '┃',
+    slide: '◇',
+    skid: '╳',
+    skid_travel: '═',
+    wall: '│',
+    rule: '─',
+    top: ['┌', '┐'],
+    separator: ['├', '┤'],
+    bottom: ['└', '┘'],
+};
+
+const ASCII: Glyphs = Glyphs {
+    left: '<',
+    right: '>',
+    jump: '^',
+    down: 'v',
+    hold: '|',
+    slide: 'o',
+    skid: 'x',
+    skid_travel: '=',
+    wall: '|',
+    rule: '-',
+    top: ['+', '+'],
+    separator: ['+', '+'],
+    bottom: ['+', '+'],
+};
+
+/// Lanes covered by `left` up to `right` in position units, 4096 to a lane.
+fn lanes(left: i32, right: i32) -> Range<usize> {
+    let lane = |position: i32| (position.clamp(0, 65536) as f64 / 4096.0).round() as usize;
+    let (left, right) = (lane(left.min(right)), lane(left.max(right)));
+    // Anything narrower than a lane still shows up in the lane it is in.
+    left.min(LANES - 1)..right.max(left + 1).min(LANES)
+}
+
+/// Lanes covered by a note at `lane` that is `width` lanes wide.
+fn note_lanes(lane: i32, width: i32) -> Range<usize> {
+    lanes(lane * 4096, (lane + width) * 4096)
+}
+
+fn fill(cells: &mut [char], lanes: Range<usize>, glyph: char) {
+    for cell in &mut cells[lanes] {
+        *cell = glyph;
+    }
+}
+
+/// A chart laid out as a lane grid for reading in a terminal: one row per
+/// tick with notes, plus one at each beat, and the time of each row in
+/// milliseconds down the left.
+pub struct GridView<'a> {
+    chart: &'a Chart,
+    timeline: Timeline,
+    holds: Vec<Step>,
+    glyphs: &'static Glyphs,
+}
+
+impl<'a> GridView<'a> {
+    /// Fails if a hold start, waypoint or end can't be paired up.
+    pub fn new(chart: &'a Chart, style: GridStyle) -> Result<GridView<'a>, HoldError> {
+        let timeline = Timeline::new(chart);
+        let holds = chart
+            .drs_steps(&timeline)?
+            .into_iter()
+            .filter(|step| step.category == 1)
+            .collect();
+        Ok(GridView {
+            chart,
+            timeline,
+            holds,
+            glyphs: match style {
+                GridStyle::Unicode => &UNICODE,
+                GridStyle::Ascii => &ASCII,
+            },
+        })
+    }
+
+    /// Measures in the chart, and so the end of the range [`GridView::page`]
+    /// can show.
+    pub fn measure_count(&self) -> usize {
+        self.chart.measures.len()
+    }
+
+    /// Hold bodies, and the sideways moves of skids, on the row at `ms`.
+    fn hold_cells(&self, cells: &mut [char], ms: i64) {
+        let glyphs = self.glyphs;
+        for hold in &self.holds {
+            if ms < hold.stime_ms || ms > hold.etime_ms {
+                continue;
+            }
+            let (mut left, mut right, mut from_ms) = (hold.pos_left, hold.pos_right, hold.stime_ms);
+            for point in &hold.long_point {
+                if ms < point.time && ms > from_ms {
+                    // Slides move across the pad between their points.
+                    let along = (ms - from_ms) as f64 / (point.time - from_ms) as f64;
+                    let at = |from: i32, to: i32| from + ((to - from) as f64 * along) as i32;
+                    let span = lanes(at(left, point.pos_left), at(right, point.pos_right));
+                    fill(cells, span, glyphs.hold);
+                    break;
+                }
+                (left, right, from_ms) = (point.pos_left, point.pos_right, point.time);
+                if let Some((skid_left, skid_right)) = point.skid_end {
+                    if ms == point.time {
+                        let from = lanes(left, right);
+                        let to = lanes(skid_left, skid_right);
+                        let travel = from.start.min(to.start)..from.end.max(to.end);
+                        fill(cells, travel, glyphs.skid_travel);
+                        fill(cells, to, glyphs.skid);
+                    }
+                    (left, right) = (skid_left, skid_right);
+                }
+            }
+        }
+    }
+
+    /// Notes on one tick, drawn over any hold bodies.
+    fn note_cells(&self, cells: &mut [char], notes: &[TimedEvent]) {
+        let glyphs = self.glyphs;
+        // Jumps and downs go underneath, so steps on the same tick show.
+        let (across, spans): (Vec<&TimedEvent>, Vec<_>) =
+            notes.iter().partition(|note| note.event.spans().is_empty());
+        for note in across.into_iter().chain(spans) {
+            let glyph = match note.event {
+                NoteEvent::LeftStep { .. } | NoteEvent::LeftHoldStart { .. } => glyphs.left,
+                NoteEvent::RightStep { .. } | NoteEvent::RightHoldStart { .. } => glyphs.right,
+                NoteEvent::SlideWaypoint { .. } | NoteEvent::SlideEnd { .. } => glyphs.slide,
+                NoteEvent::Jump => glyphs.jump,
+                NoteEvent::Down => glyphs.down,
+                // Skids are drawn from their hold, which knows where they
+                // move from.
+                _ => continue,
+            };
+            match note.event.spans().first() {
+                Some(&(lane, width)) => fill(cells, note_lanes(lane.into(), width.into()), glyph),
+                None => fill(cells, 0..LANES, glyph),
+            }
+        }
+    }
+
+    fn rule(&self, output: &mut String, label: &str, [left, right]: [char; 2]) {
+        let rule: String = std::iter::repeat_n(self.glyphs.rule, LANES).collect();
+        writeln!(output, "{:>9} {}{}{}", label, left, rule, right).unwrap();
+    }
+
+    /// Draws `measures`, which may run past the end of the chart, with a
+    /// line above each one and one closing the last.
+    pub fn page(&self, measures: Range<usize>) -> String {
+        let glyphs = self.glyphs;
+        let mut output = String::new();
+        for measure_num in measures.clone() {
+            let corners = if measure_num == measures.start {
+                glyphs.top
+            } else {
+                glyphs.separator
+            };
+            self.rule(&mut output, &format!("m{}", measure_num), corners);
+
+            let measure = self.chart.measures.get(measure_num);
+            let resolution = measure.map_or(TICKS_PER_MEASURE, |measure| measure.resolution);
+            let beats = measure.map_or(4, |measure| measure.signature.num as usize);
+            let mut rows: Vec<usize> = (0..beats)
+                .filter(|beat| (beat * resolution).is_multiple_of(beats))
+                .map(|beat| beat * resolution / beats)
+                .collect();
+            let ticks: Vec<&[TimedEvent]> = self
+                .chart
+                .events
+                .ticks()
+                .skip_while(|tick| tick[0].measure < measure_num)
+                .take_while(|tick| tick[0].measure == measure_num)
+                .collect();
+            rows.extend(ticks.iter().map(|tick| tick[0].tick));
+            rows.sort_unstable();
+            rows.dedup();
+
+            for tick in rows {
+                let ms = self.timeline.ms_at(measure_num, tick);
+                let mut cells = [' '; LANES];
+                self.hold_cells(&mut cells, ms);
+                if let Some(notes) = ticks.iter().find(|notes| notes[0].tick == tick) {
+                    self.note_cells(&mut cells, notes);
+                }
+                let cells: String = cells.iter().collect();
+                writeln!(output, "{:>9} {}{}{}", ms, glyphs.wall, cells, glyphs.wall).unwrap();
+            }
+        }
+        self.rule(&mut output, &format!("m{}", measures.end), glyphs.bottom);
+        output
+    }
+}
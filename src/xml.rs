@@ -0,0 +1,54 @@
+use xml_builder::{XMLBuilder, XMLElement, XMLVersion};
+
+pub(crate) fn add_s32_element(xml: &mut XMLElement, name: &str, value: i32) {
+    let mut element = XMLElement::new(name);
+    element.add_attribute("__type", "s32");
+    element.add_text(value.to_string()).unwrap();
+    xml.add_child(element).unwrap();
+}
+
+pub(crate) fn add_s64_element(xml: &mut XMLElement, name: &str, value: i64) {
+    let mut element = XMLElement::new(name);
+    element.add_attribute("__type", "s64");
+    element.add_text(value.to_string()).unwrap();
+    xml.add_child(element).unwrap();
+}
+
+pub(crate) fn add_u32_element(xml: &mut XMLElement, name: &str, value: u32) {
+    let mut element = XMLElement::new(name);
+    element.add_attribute("__type", "u32");
+    element.add_text(value.to_string()).unwrap();
+    xml.add_child(element).unwrap();
+}
+
+pub(crate) fn add_u8_element(xml: &mut XMLElement, name: &str, value: u8) {
+    let mut element = XMLElement::new(name);
+    element.add_attribute("__type", "u8");
+    element.add_text(value.to_string()).unwrap();
+    xml.add_child(element).unwrap();
+}
+
+/// Escapes element text; `xml_builder` only escapes attribute values.
+pub(crate) fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub(crate) fn add_str_element(xml: &mut XMLElement, name: &str, value: &str) {
+    let mut element = XMLElement::new(name);
+    element.add_attribute("__type", "str");
+    element.add_text(escape_text(value)).unwrap();
+    xml.add_child(element).unwrap();
+}
+
+pub(crate) fn render(root: XMLElement) -> String {
+    let mut builder = XMLBuilder::new()
+        .version(XMLVersion::XML1_0)
+        .encoding("UTF-8".into())
+        .build();
+    builder.set_root_element(root);
+    let mut writer = Vec::<u8>::new();
+    builder.generate(&mut writer).unwrap();
+    String::from_utf8(writer).unwrap()
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+use std::process::Command;
+
+fn write(path: &Path, contents: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn a_bad_chart_does_not_stop_the_others() {
+    let dir = std::env::temp_dir().join(format!("drs-converter-batch-{}", std::process::id()));
+    let (charts, out) = (dir.join("charts"), dir.join("out"));
+    let chart = |song_id, difficulty| {
+        format!(
+            "#SONGID {}\n#DIFFICULTY {}\n#BPM01: 120\n#START\n0\n0:003\nEND\n",
+            song_id, difficulty
+        )
+    };
+    write(&charts.join("a.ssf"), &chart("1", 2));
+    write(
+        &charts.join("b.ssf"),
+        "#BPM01: 120\n#START\n0\n0:zz3\nEND\n",
+    );
+    write(&charts.join("sub/c.ssf"), &chart("2", 0));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_drs-converter"))
+        .arg("batch")
+        .arg(&charts)
+        .arg("-o")
+        .arg(&out)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let converted = (
+        out.join("1_1c.xml").is_file(),
+        out.join("sub/2_1a.xml").is_file(),
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(converted, (true, true));
+    assert_eq!(
+        stdout,
+        "status  chart      result
+ok      a.ssf      1_1c.xml
+failed  b.ssf      line 4, column 3: unknown note type 'z' (at 'z')
+ok      sub/c.ssf  sub/2_1a.xml
+2 converted, 1 failed
+"
+    );
+    assert_eq!(stderr, "error: 1 of 3 charts failed\n");
+}
@@ -0,0 +1,6 @@
+use drs_converter::{parse_ssf, Chart};
+
+/// Parses a chart at 120 BPM from `notes`, its body up to `END`.
+pub fn chart(notes: &str) -> Chart {
+    parse_ssf(&format!("#BPM01: 120\n#START\n{}END\n", notes)).unwrap()
+}
@@ -0,0 +1,239 @@
+use drs_converter::{parse_drs_xml, parse_ssf, DrsReadError, DrsWriteError};
+
+const SOURCE: &str = "#BPM01: 120
+#START
+0
+0:003
+1
+0:103
+END
+";
+
+fn xml() -> String {
+    parse_ssf(SOURCE).unwrap().to_drs_xml().unwrap()
+}
+
+#[test]
+fn times_out_of_range_are_rejected() {
+    let measure_dt = "<delta_time __type=\"s32\">0</delta_time>\n\t\t\t\t<num";
+    for delta_time in ["1e300", "inf", "NaN", "4000000000"] {
+        let broken = xml().replacen(
+            measure_dt,
+            &format!(
+                "<delta_time __type=\"s32\">{}</delta_time>\n\t\t\t\t<num",
+                delta_time
+            ),
+            1,
+        );
+        assert!(matches!(
+            parse_drs_xml(&broken).unwrap_err(),
+            DrsReadError::InvalidValue {
+                element: "delta_time",
+                ..
+            }
+        ));
+    }
+
+    // In range for an s32, but far past the last measure a chart can have.
+    let far_measure = "<measure>
+<time __type=\"s32\">2000000000</time>
+<delta_time __type=\"s32\">2000000000</delta_time>
+<num __type=\"s32\">3</num>
+<denomi __type=\"s32\">4</denomi>
+</measure>
+</measure_info>";
+    let broken = xml().replacen("</measure_info>", far_measure, 1);
+    assert_eq!(
+        parse_drs_xml(&broken).unwrap_err(),
+        DrsReadError::TooManyMeasures
+    );
+
+    let broken = xml().replacen(
+        "<bpm __type=\"s32\">12000</bpm>",
+        "<bpm __type=\"s32\">0</bpm>",
+        1,
+    );
+    assert_eq!(
+        parse_drs_xml(&broken).unwrap_err(),
+        DrsReadError::InvalidValue {
+            element: "bpm",
+            value: "0".to_string()
+        }
+    );
+}
+
+#[test]
+fn ticks_per_beat_default_only_when_missing() {
+    let tick = "<tick __type=\"s32\">480</tick>";
+    assert_eq!(
+        parse_drs_xml(&xml().replacen(tick, "", 1)).unwrap(),
+        parse_drs_xml(&xml()).unwrap()
+    );
+    for value in ["abc", "0", "-480"] {
+        let broken = xml().replacen(tick, &format!("<tick __type=\"s32\">{}</tick>", value), 1);
+        assert!(matches!(
+            parse_drs_xml(&broken).unwrap_err(),
+            DrsReadError::InvalidValue {
+                element: "tick",
+                ..
+            }
+        ));
+    }
+}
+
+#[test]
+fn padding_and_offset_survive_a_round_trip() {
+    for (bpm, offset) in [
+        ("173", "-0.05"),
+        ("173", "0.9"),
+        ("120", "12.345"),
+        ("99.99", "-0.7"),
+    ] {
+        let source = format!(
+            "#BPM01: {}\n#WAVEOFFSET {}\n#00008: 2\n#START\n0\n0:003\n1\n5:103\nEND\n",
+            bpm, offset
+        );
+        let xml = parse_ssf(&source).unwrap().to_drs_xml().unwrap();
+        let imported = parse_drs_xml(&xml).unwrap();
+        assert_eq!(
+            imported.to_drs_xml().unwrap(),
+            xml,
+            "{} BPM, {} s",
+            bpm,
+            offset
+        );
+    }
+
+    // Whole bars stay padding bars, with only the rest as the offset.
+    let source = "#BPM01: 173\n#WAVEOFFSET -0.05\n#00008: 2\n#START\n0\n0:003\nEND\n";
+    let xml = parse_ssf(source).unwrap().to_drs_xml().unwrap();
+    let header = parse_drs_xml(&xml).unwrap().header;
+    assert_eq!(
+        (header.padding_bars, header.wave_offset),
+        (Some(2), Some(-50))
+    );
+}
+
+#[test]
+fn times_past_an_s32_are_errors() {
+    let source = "#WAVEOFFSET 2147483\n#BPM01: 120\n#00008: 4294967295\n#START\n0\n0:003\nEND\n";
+    assert_eq!(
+        parse_ssf(source).unwrap().to_drs_xml().unwrap_err(),
+        DrsWriteError::TimeOutOfRange {
+            element: "time",
+            value: 8592082073000
+        }
+    );
+}
+
+#[test]
+fn delta_times_past_an_s32_are_errors() {
+    // Fast enough that the milliseconds still fit.
+    let source = "#BPM01: 1000\n#00008: 2000000\n#START\n0\n0:003\nEND\n";
+    assert_eq!(
+        parse_ssf(source).unwrap().to_drs_xml().unwrap_err(),
+        DrsWriteError::TimeOutOfRange {
+            element: "delta_time",
+            value: 3840000000
+        }
+    );
+}
+
+#[test]
+fn finer_grids_survive_a_round_trip() {
+    let source = "#BPM01: 120
+#BPM02: 99.99
+#00208: 0002
+#START
+0
+0:003
+2 384
+1:103,4203
+191:1C3
+383:023,7213
+3 576
+5:0A3
+4
+0:2
+END
+";
+    let mut chart = parse_ssf(source).unwrap();
+    let xml = chart.to_drs_xml().unwrap();
+    let mut imported = parse_drs_xml(&xml).unwrap();
+    assert_eq!(imported.to_drs_xml().unwrap(), xml);
+    chart.normalize().unwrap();
+    imported.normalize().unwrap();
+    assert_eq!(imported, chart);
+
+    // A lead-in that puts the first measure between two milliseconds.
+    let source = "#BPM01: 99.99\n#WAVEOFFSET 3\n#START\n0\n0:003\n1 384\n3:103\nEND\n";
+    let chart = parse_ssf(source).unwrap();
+    let xml = chart.to_drs_xml().unwrap();
+    assert_eq!(parse_drs_xml(&xml).unwrap().to_drs_xml().unwrap(), xml);
+}
+
+/// The `fields` of each `element` under `list`, in order.
+fn rows(xml: &str, list: &str, element: &str, fields: &[&str]) -> Vec<Vec<i64>> {
+    let document = roxmltree::Document::parse(xml).unwrap();
+    let list = document
+        .descendants()
+        .find(|node| node.has_tag_name(list))
+        .unwrap();
+    list.children()
+        .filter(|node| node.has_tag_name(element))
+        .map(|node| {
+            fields
+                .iter()
+                .map(|field| {
+                    let child = node.children().find(|child| child.has_tag_name(*field));
+                    child.unwrap().text().unwrap().parse().unwrap()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// 180 BPM from halfway through measure 1, and measure 2 in 7/8.
+const CHANGES: &str = "#BPM01: 120
+#BPM02: 180
+#00108: 0002
+#00202: 7/8
+#00302: 4
+#START
+0
+0:003
+3
+0:103
+END
+";
+
+#[test]
+fn tempo_changes_are_listed() {
+    let xml = parse_ssf(CHANGES).unwrap().to_drs_xml().unwrap();
+    // The change is on beat 6, 3 s in at 120 BPM.
+    assert_eq!(
+        rows(&xml, "bpm_info", "bpm", &["time", "delta_time", "bpm"]),
+        vec![vec![0, 0, 12000], vec![3000, 2880, 18000]]
+    );
+}
+
+#[test]
+fn signature_changes_are_listed() {
+    let xml = parse_ssf(CHANGES).unwrap().to_drs_xml().unwrap();
+    // Measure 2 starts on beat 8 and measure 3 on beat 11.5, with every beat
+    // after the tempo change taking 333.33 ms. Measure 1 keeps 4/4, so it
+    // isn't listed.
+    assert_eq!(
+        rows(
+            &xml,
+            "measure_info",
+            "measure",
+            &["time", "delta_time", "num", "denomi"]
+        ),
+        vec![
+            vec![0, 0, 4, 4],
+            vec![3667, 3840, 7, 8],
+            vec![4833, 5520, 4, 4]
+        ]
+    );
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::chart;
+use drs_converter::{HoldError, NoteEvent};
+
+#[test]
+fn an_id_can_start_again_on_the_tick_its_hold_ends() {
+    // The end is listed after the new start, but still closes the first
+    // hold.
+    let holds = chart("0\n0:4103\n96:4183,7103\n1\n0:7183\n")
+        .holds()
+        .unwrap();
+    assert_eq!(holds.len(), 2);
+    assert_eq!((holds[0].measure, holds[0].tick), (0, 0));
+    assert_eq!(holds[0].end().position(), (0, 96));
+    assert_eq!(
+        holds[0].end().event,
+        NoteEvent::SlideEnd {
+            id: 1,
+            lane: 0,
+            width: 4
+        }
+    );
+    assert_eq!((holds[1].measure, holds[1].tick), (0, 96));
+    assert_eq!(holds[1].end().position(), (1, 0));
+}
+
+#[test]
+fn a_hold_can_end_on_the_tick_it_starts() {
+    // With no hold open for the id beforehand, the end goes to the hold
+    // starting alongside it.
+    let holds = chart("0\n0:7103,4103\n").holds().unwrap();
+    assert_eq!(holds.len(), 1);
+    assert_eq!(holds[0].points.len(), 1);
+    assert_eq!(holds[0].end().position(), (0, 0));
+}
+
+#[test]
+fn waypoints_and_ends_need_an_open_hold() {
+    assert_eq!(
+        chart("0\n0:4103\n48:7103\n96:6203\n").holds(),
+        Err(HoldError::OrphanWaypoint {
+            measure: 0,
+            tick: 96,
+            id: 2
+        })
+    );
+    // Once a hold ends, its id is no longer open.
+    assert_eq!(
+        chart("0\n0:4103\n48:7103\n96:7103\n").holds(),
+        Err(HoldError::OrphanEnd {
+            measure: 0,
+            tick: 96,
+            id: 1
+        })
+    );
+}
+
+#[test]
+fn holds_must_end() {
+    assert_eq!(
+        chart("0\n0:4103\n48:6143\n").holds(),
+        Err(HoldError::Unterminated {
+            measure: 0,
+            tick: 0,
+            id: 1
+        })
+    );
+    // Starting an id again before its hold ends leaves the first one open.
+    assert_eq!(
+        chart("0\n0:4103\n48:5143\n96:7143\n").holds(),
+        Err(HoldError::Unterminated {
+            measure: 0,
+            tick: 0,
+            id: 1
+        })
+    );
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "serde")]
+
+use drs_converter::{parse_ssf, Chart, JsonError, JSON_SCHEMA_VERSION};
+
+const SOURCE: &str = "#TITLE Round trip
+#DIFFICULTY 1
+#BPM01: 150
+#BPM02: 75.5
+#00008: 1
+#00102: 7/8
+#00108: 000002
+#START
+0
+0:003,41A3
+96:71A3,2
+1 480
+0:4023,3
+7:B0C3D3
+END
+";
+
+#[test]
+fn charts_survive_a_trip_through_json() {
+    let mut chart = parse_ssf(SOURCE).unwrap();
+    let json = chart.to_json().unwrap();
+    assert!(json.starts_with(&format!("{{\n  \"version\": {},", JSON_SCHEMA_VERSION)));
+
+    // Holds are listed apart from other notes, so only the order of notes
+    // within a tick can change.
+    let mut read = Chart::from_json(&json).unwrap();
+    chart.normalize().unwrap();
+    read.normalize().unwrap();
+    assert_eq!(read, chart);
+    assert_eq!(read.to_drs_xml().unwrap(), chart.to_drs_xml().unwrap());
+}
+
+#[test]
+fn other_schema_versions_are_rejected() {
+    let json = parse_ssf(SOURCE).unwrap().to_json().unwrap();
+    let future = json.replacen(
+        &format!("\"version\": {}", JSON_SCHEMA_VERSION),
+        "\"version\": 99",
+        1,
+    );
+    assert_eq!(
+        Chart::from_json(&future).unwrap_err(),
+        JsonError::UnsupportedVersion(99)
+    );
+}
+
+#[test]
+fn holds_are_checked_on_the_way_in() {
+    let json = parse_ssf(SOURCE).unwrap().to_json().unwrap();
+    let broken = json.replacen(
+        "\"type\": \"ComplexSkidEnd\"",
+        "\"type\": \"ComplexSkidWaypoint\"",
+        1,
+    );
+    assert!(matches!(
+        Chart::from_json(&broken).unwrap_err(),
+        JsonError::Hold(_)
+    ));
+}
+
+#[test]
+fn lanes_and_widths_are_checked_on_the_way_in() {
+    let json = parse_ssf("#BPM01: 120\n#START\n2\n5:003\nEND\n")
+        .unwrap()
+        .to_json()
+        .unwrap();
+    for (lane, width) in [(250, 10), (15, 17), (0, 0)] {
+        let broken = json
+            .replacen("\"lane\": 0", &format!("\"lane\": {}", lane), 1)
+            .replacen("\"width\": 4", &format!("\"width\": {}", width), 1);
+        assert_eq!(
+            Chart::from_json(&broken).unwrap_err(),
+            JsonError::InvalidLane {
+                measure: 2,
+                tick: 5
+            }
+        );
+    }
+}
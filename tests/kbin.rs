@@ -0,0 +1,73 @@
+use drs_converter::{kbin_to_xml, music_list_xml, parse_ssf, xml_to_kbin};
+
+const CHART: &str = "#TITLE Sample & Song
+#ARTIST Someone
+#DESIGNER Me
+#DIFFICULTY 2
+#PLAYLEVEL 9+
+#SONGID 1234
+#WAVE song.ogg
+#WAVEOFFSET -0.25
+#BPM01: 173
+#BPM02: 86.5
+#00008: 1
+#00208: 0002
+#START
+0
+0:003,1C3
+48:2
+96:3
+144:41A3
+1
+0:61A3,5205
+48:71A3
+96:8230
+180:A250
+2
+0:4Z03,5Y83
+96:9Z0322,6Y81
+120:BZ2345
+191:7Y81
+END
+";
+
+#[test]
+fn sequence_round_trips_through_kbin() {
+    let xml = parse_ssf(CHART).unwrap().to_drs_xml().unwrap();
+    let kbin = xml_to_kbin(&xml).unwrap();
+    assert_eq!(kbin_to_xml(&kbin).unwrap(), xml);
+}
+
+#[test]
+fn music_list_round_trips_through_kbin() {
+    let xml = music_list_xml(&[parse_ssf(CHART).unwrap()]).unwrap();
+    let kbin = xml_to_kbin(&xml).unwrap();
+    assert_eq!(kbin_to_xml(&kbin).unwrap(), xml);
+}
+
+#[test]
+fn encodes_header_names_and_data() {
+    let kbin = xml_to_kbin(r#"<a __type="s32">1</a>"#).unwrap();
+    assert_eq!(
+        kbin,
+        [
+            0xA0, 0x42, 0xA0, 0x5F, // signature, compressed names, UTF-8
+            0, 0, 0, 8, // node section length
+            0x06, 0x01, 0x98, 0xFE, 0xFF, 0, 0, 0, // s32 "a", end, end of section
+            0, 0, 0, 4, // data section length
+            0, 0, 0, 1,
+        ]
+    );
+}
+
+#[test]
+fn packs_small_values_into_shared_words() {
+    let xml = r#"<r><a __type="u8">1</a><b __type="s32">2</b><c __type="u8">3</c><d __type="s16">-2</d></r>"#;
+    let kbin = xml_to_kbin(xml).unwrap();
+    let data = &kbin[kbin.len() - 12..];
+    assert_eq!(data, [1, 3, 0, 0, 0, 0, 0, 2, 0xFF, 0xFE, 0, 0]);
+    assert_eq!(
+        kbin_to_xml(&kbin).unwrap(),
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<r>\n\t<a __type=\"u8\">1</a>\n\t<b __type=\"s32\">2</b>\n\t<c __type=\"u8\">3</c>\n\t<d __type=\"s16\">-2</d>\n</r>\n"
+    );
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::chart;
+use drs_converter::{diagnostics_json, lint, Diagnostic, Rule, Severity};
+
+/// Lints `notes`, the ticks of measure 0.
+fn lint_notes(notes: &str) -> Vec<Diagnostic> {
+    lint(&chart(&format!("0\n{}", notes)))
+}
+
+/// The rule and position of each diagnostic.
+fn found(notes: &str) -> Vec<(Rule, usize)> {
+    lint_notes(notes)
+        .iter()
+        .map(|diagnostic| (diagnostic.rule, diagnostic.tick))
+        .collect()
+}
+
+#[test]
+fn clean_charts_pass() {
+    assert_eq!(found("0:003,1C3\n48:4103\n96:7143,1C3\n144:2\n"), vec![]);
+}
+
+#[test]
+fn lane_out_of_range() {
+    assert_eq!(found("0:0F3\n"), vec![(Rule::LaneOutOfRange, 0)]);
+}
+
+#[test]
+fn overlapping_notes() {
+    assert_eq!(found("0:003,023,123\n"), vec![(Rule::OverlappingNotes, 0)]);
+}
+
+#[test]
+fn step_in_hold() {
+    assert_eq!(
+        found("0:4103\n48:023,1C3\n96:7103\n"),
+        vec![(Rule::StepInHold, 48)]
+    );
+}
+
+#[test]
+fn zero_length_hold() {
+    let diagnostics = lint_notes("0:4103,7103\n");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule, Rule::ZeroLengthHold);
+    assert_eq!(diagnostics[0].severity(), Severity::Warning);
+}
+
+#[test]
+fn waypoint_order() {
+    assert_eq!(
+        found("0:4103\n48:6103,6113\n96:7103\n"),
+        vec![(Rule::WaypointOrder, 48)]
+    );
+}
+
+#[test]
+fn stacked_jump_down() {
+    assert_eq!(found("0:2,3\n"), vec![(Rule::StackedJumpDown, 0)]);
+}
+
+#[test]
+fn unpaired_hold() {
+    assert_eq!(found("0:003\n96:7103\n"), vec![(Rule::UnpairedHold, 96)]);
+    assert_eq!(
+        lint_notes("0:003\n96:7103\n")[0].to_string(),
+        "error[unpaired-hold] measure 0, tick 96: end of hold 1 with no open hold"
+    );
+}
+
+#[test]
+fn diagnostics_as_json() {
+    assert_eq!(diagnostics_json(&[]), "[]\n");
+    assert_eq!(
+        diagnostics_json(&lint_notes("0:2,3\n96:4103,7103\n")),
+        r#"[
+  {"rule": "stacked-jump-down", "severity": "error", "measure": 0, "tick": 0, "message": "2 jumps and downs on one tick"},
+  {"rule": "zero-length-hold", "severity": "warning", "measure": 0, "tick": 96, "message": "hold 1 ends on the tick it starts"}
+]
+"#
+    );
+}
@@ -0,0 +1,123 @@
+use drs_converter::{
+    check_song, check_song_id, music_list_xml, parse_ssf, Chart, Difficulty, MetadataError,
+};
+
+#[test]
+fn song_ids_must_be_plain_file_names() {
+    for song_id in ["1234", "song_01", "a.b"] {
+        assert_eq!(check_song_id(song_id), Ok(()));
+    }
+    for song_id in [
+        "",
+        "..",
+        "../../esc/x",
+        "a/b",
+        "a\\b",
+        "/tmp/abs",
+        "C:x",
+        "a..b",
+    ] {
+        assert_eq!(
+            check_song_id(song_id),
+            Err(MetadataError::InvalidSongId {
+                song_id: song_id.to_string()
+            })
+        );
+    }
+}
+
+#[test]
+fn packaged_songs_check_their_song_id() {
+    let chart = parse_ssf("#SONGID /tmp/abs\n#DIFFICULTY 2\n#BPM01: 120\n#START\nEND\n").unwrap();
+    let song_id = chart.header.song_id.clone().unwrap();
+    assert_eq!(
+        check_song(&song_id, &[chart]),
+        Err(MetadataError::InvalidSongId { song_id })
+    );
+}
+
+fn song(song_id: &str, difficulty: u8, header: &str) -> Chart {
+    parse_ssf(&format!(
+        "#SONGID {}\n#DIFFICULTY {}\n{}#START\nEND\n",
+        song_id, difficulty, header
+    ))
+    .unwrap()
+}
+
+/// The text of the element at `path` under `node`, or `None` if it's missing.
+fn text<'a>(node: roxmltree::Node<'a, '_>, path: &[&str]) -> Option<&'a str> {
+    let mut node = node;
+    for name in path {
+        node = node.children().find(|child| child.has_tag_name(*name))?;
+    }
+    Some(node.text().unwrap_or(""))
+}
+
+#[test]
+fn charts_of_one_song_share_a_music_entry() {
+    let charts = [
+        song(
+            "1234",
+            2,
+            "#TITLE Song\n#PLAYLEVEL 12+\n#DESIGNER someone\n#BPM01: 150\n",
+        ),
+        song("9", 0, "#TITLE Other\n#BPM01: 100\n"),
+        song(
+            "1234",
+            0,
+            "#TITLE Song\n#ARTIST Band\n#PLAYLEVEL 3\n#BPM01: 120\n#BPM02: 180\n#00108: 02\n",
+        ),
+    ];
+    let xml = music_list_xml(&charts).unwrap();
+    let document = roxmltree::Document::parse(&xml).unwrap();
+    let music: Vec<_> = document
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("music"))
+        .collect();
+    let ids: Vec<_> = music
+        .iter()
+        .map(|node| node.attribute("id").unwrap())
+        .collect();
+    assert_eq!(ids, ["1234", "9"]);
+
+    // The later chart fills in the artist the first one left out, and the
+    // BPM range covers both.
+    let entry = music[0];
+    assert_eq!(text(entry, &["info", "title_name"]), Some("Song"));
+    assert_eq!(text(entry, &["info", "artist_name"]), Some("Band"));
+    assert_eq!(text(entry, &["info", "bpm_max"]), Some("18000"));
+    assert_eq!(text(entry, &["info", "bpm_min"]), Some("12000"));
+    assert_eq!(
+        text(entry, &["difficulty", "fumen_1a", "difnum"]),
+        Some("3")
+    );
+    assert_eq!(text(entry, &["difficulty", "fumen_1b", "difnum"]), None);
+    assert_eq!(
+        text(entry, &["difficulty", "fumen_1c", "difnum"]),
+        Some("12")
+    );
+    assert_eq!(
+        text(entry, &["difficulty", "fumen_1c", "designer"]),
+        Some("someone")
+    );
+    assert_eq!(
+        text(music[1], &["difficulty", "fumen_1a", "difnum"]),
+        Some("0")
+    );
+}
+
+#[test]
+fn a_difficulty_already_in_the_entry_is_an_error() {
+    let charts = [
+        song("1234", 1, "#BPM01: 120\n"),
+        song("1234", 1, "#BPM01: 120\n"),
+    ];
+    assert_eq!(
+        music_list_xml(&charts),
+        Err(MetadataError::DuplicateDifficulty {
+            song_id: "1234".to_string(),
+            difficulty: Difficulty::Normal
+        })
+    );
+}
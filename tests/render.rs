@@ -0,0 +1,35 @@
+use drs_converter::parse_ssf;
+
+#[test]
+fn pages_split_the_chart_by_measure() {
+    let chart = parse_ssf(
+        "#BPM01: 120
+#START
+0
+0:003,2
+96:41A3
+1
+0:6183
+2
+0:8183
+48:A1A3
+4
+0:133
+END
+",
+    )
+    .unwrap();
+    let pages = chart.to_svg_pages(2).unwrap();
+    assert_eq!(pages.len(), 3);
+    assert!(pages
+        .iter()
+        .all(|page| page.starts_with("<svg ") && page.ends_with("</svg>\n")));
+
+    // The hold crosses the first two pages: three stretches, plus a
+    // slanted band for each skid.
+    for page in &pages[..2] {
+        assert_eq!(page.matches("<polygon").count(), 5);
+    }
+    assert_eq!(pages[2].matches("<polygon").count(), 0);
+    assert!(pages[2].contains(">4</text>"));
+}
@@ -0,0 +1,162 @@
+use drs_converter::{parse_ssf, ParseErrorKind, TimeSignature, WriteError};
+
+const CLEAN: &str = "#TITLE THE END
+#BPM01: 173
+#BPM02: 200
+#00208: 0002
+#START
+0
+0:003,1C3
+2 384
+100:41A3
+101:71A3
+END
+";
+
+#[test]
+fn comments_blank_lines_and_windows_line_endings_are_ignored() {
+    let messy = "\u{feff}// exported by hand\r\n\
+                 #TITLE THE END\t\r\n\
+                 \r\n\
+                 #BPM01:173\r\n\
+                 #BPM02: 200   \r\n\
+                 #00208:0002\r\n\
+                 \x20 #START\r\n\
+                 // intro\r\n\
+                 0\r\n\
+                 \x20 0:003 , 1C3 // both feet\r\n\
+                 \r\n\
+                 2   384\r\n\
+                 100:41A3\r\n\
+                 101:71A3\r\n\
+                 END\r\n";
+    assert_eq!(parse_ssf(messy).unwrap(), parse_ssf(CLEAN).unwrap());
+}
+
+#[test]
+fn header_and_data_are_told_apart_by_syntax() {
+    let chart = parse_ssf(CLEAN).unwrap();
+    assert_eq!(chart.header.title.as_deref(), Some("THE END"));
+    assert_eq!(chart.events.len(), 4);
+
+    let error = parse_ssf("#BPM01: 120\n0:003\n#START\n").unwrap_err();
+    assert_eq!((error.line, error.kind), (2, ParseErrorKind::DataBeforeStart));
+    let error = parse_ssf("#BPM01: 120\n#START\n0\n#TITLE late\n").unwrap_err();
+    assert_eq!((error.line, error.kind), (4, ParseErrorKind::CommandAfterStart));
+    let error = parse_ssf("#BPM01: 120\n#START\n0\n0:003 ?\n").unwrap_err();
+    assert_eq!(
+        (error.line, error.column, error.kind),
+        (4, 6, ParseErrorKind::TrailingCharacters)
+    );
+}
+
+#[test]
+fn written_charts_parse_back_the_same() {
+    let source = "#TITLE Round trip
+#ARTIST Someone
+#DIFFICULTY 2
+#WAVEOFFSET -0.125
+#BPM01: 150
+#BPM02: 75.5
+#00008: 2
+#00102: 3
+#00108: 000002
+#START
+0
+0:003,1C3,2
+96:41A3,8145,91A3B3
+1 480
+0:B1C3D3,3
+5
+END
+";
+    let chart = parse_ssf(source).unwrap();
+    let written = chart.to_ssf().unwrap();
+    assert_eq!(parse_ssf(&written).unwrap(), chart);
+    assert_eq!(parse_ssf(&written).unwrap().to_ssf().unwrap(), written);
+}
+
+#[test]
+fn normalize_sorts_notes_and_renumbers_holds() {
+    let mut chart = parse_ssf(
+        "#BPM01: 120
+#START
+0
+0:45A3,003
+48:75A3,4Z23
+96:7Z23
+END
+",
+    )
+    .unwrap();
+    chart.normalize().unwrap();
+    assert_eq!(
+        chart.to_ssf().unwrap(),
+        "#BPM01: 120
+#START
+0
+0:003,40A3
+48:4123,70A3
+96:7123
+END
+"
+    );
+}
+
+#[test]
+fn measure_numbers_past_the_last_allowed_are_errors() {
+    for number in ["100000", "4000000000", "18446744073709551615"] {
+        let source = format!("#BPM01: 120\n#START\n0\n0:003\n{}\n0:003\nEND\n", number);
+        let error = parse_ssf(&source).unwrap_err();
+        assert_eq!((error.line, error.column), (5, 1));
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::MeasureOutOfRange { max: 99999, .. }
+        ));
+    }
+
+    // Skipped measures are still there, empty.
+    let chart = parse_ssf("#BPM01: 120\n#START\n1500\n0:003\nEND\n").unwrap();
+    assert_eq!(chart.measures.len(), 1501);
+    assert_eq!(chart.events.len(), 1);
+    assert_eq!(parse_ssf(&chart.to_ssf().unwrap()).unwrap(), chart);
+
+    // Only the header's channels are limited to three digits.
+    let error = parse_ssf("#BPM01: 120\n#100002: 3/4\n#START\nEND\n").unwrap_err();
+    assert_eq!(
+        (error.line, error.column, error.kind),
+        (
+            2,
+            2,
+            ParseErrorKind::MeasureOutOfRange {
+                measure: 1000,
+                max: 999
+            }
+        )
+    );
+    let mut chart = parse_ssf("#BPM01: 120\n#99902: 3/4\n#START\n1000\nEND\n").unwrap();
+    assert_eq!(
+        chart.measures[999].signature,
+        TimeSignature { num: 3, denomi: 4 }
+    );
+    chart.measures[1000].signature = TimeSignature::COMMON;
+    assert_eq!(
+        chart.to_ssf().unwrap_err(),
+        WriteError::ChangeOutOfRange {
+            measure: 1000,
+            max: 999
+        }
+    );
+}
+
+#[test]
+fn normalize_keeps_holds_that_start_where_another_ends() {
+    let mut chart = parse_ssf("#BPM01: 120\n#START\n0\n0:4103\n96:71C3,4283,7203\nEND\n").unwrap();
+    let before = chart.to_drs_xml().unwrap();
+    chart.normalize().unwrap();
+    assert_eq!(chart.to_drs_xml().unwrap(), before);
+    assert!(chart
+        .to_ssf()
+        .unwrap()
+        .ends_with("0\n0:4003\n96:4183,70C3,7103\nEND\n"));
+}
@@ -0,0 +1,36 @@
+use drs_converter::{parse_ssf, stats};
+
+#[test]
+fn notes_holds_and_density_are_counted() {
+    let chart = parse_ssf(
+        "#BPM01: 120
+#START
+0
+0:003,1C3
+48:4043
+96:6043,2
+144:8063
+1
+0:A083,3
+48:5283
+96:7293
+120:53C3
+168:B3C383
+END
+",
+    )
+    .unwrap();
+    let stats = stats(&chart).unwrap();
+    assert_eq!((stats.left_steps, stats.right_steps), (1, 1));
+    assert_eq!((stats.jumps, stats.downs, stats.holds), (1, 1, 3));
+    // Every waypoint and end counts towards its kind, slide ends too.
+    assert_eq!(stats.slides, 2);
+    assert_eq!((stats.simple_skids, stats.complex_skids), (2, 1));
+    assert_eq!(stats.max_combo, 7);
+    // At 2000 ms a measure: measure 0 tick 48 to measure 1 tick 0, then
+    // two holds of 48 ticks.
+    assert_eq!(stats.hold_ms, 1500 + 500 + 500);
+    assert_eq!(stats.peak_nps, 3.0);
+    assert_eq!(stats.lane_counts[0], 1);
+    assert_eq!(stats.lane_counts[8], 4);
+}
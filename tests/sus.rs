@@ -0,0 +1,83 @@
+use drs_converter::{parse_ssf, parse_sus, Bpm, ParseErrorKind};
+
+#[test]
+fn taps_and_slides_become_steps_and_holds() {
+    let sus = "#TITLE \"Slides\"
+#DIFFICULTY 1
+#BPM01: 150
+#00008: 01
+#00010: 14000000
+#00018: 0014
+#00030a: 1400
+#00034a: 0034
+#00138a: 24
+#00155: 14
+#00012: 00000034
+";
+    let ssf = "#TITLE Slides
+#DIFFICULTY 1
+#BPM01: 150
+#START
+0
+0:003,4003
+96:183,6043
+144:023
+1
+0:7083
+END
+";
+    let (chart, warnings) = parse_sus(sus).unwrap();
+    assert_eq!(chart, parse_ssf(ssf).unwrap());
+    let warnings: Vec<String> = warnings.iter().map(|warning| warning.to_string()).collect();
+    assert_eq!(
+        warnings,
+        [
+            "line 10: air notes have no DRS equivalent",
+            "line 11: flicks are imported as plain steps",
+        ]
+    );
+}
+
+#[test]
+fn notes_past_lane_15_are_left_out() {
+    let kept = "#BPM01: 150
+#00008: 01
+#0001f: 11
+";
+    let sus = format!("{}#0001g: 11\n#0003g0: 1121\n", kept);
+    let (chart, warnings) = parse_sus(&sus).unwrap();
+    assert_eq!(chart, parse_sus(kept).unwrap().0);
+    assert_eq!(chart.events.len(), 1);
+    let warnings: Vec<String> = warnings.iter().map(|warning| warning.to_string()).collect();
+    assert_eq!(
+        warnings,
+        [
+            "line 4: lanes past 15 have no DRS equivalent",
+            "line 5: lanes past 15 have no DRS equivalent",
+        ]
+    );
+}
+
+#[test]
+fn measures_past_the_last_allowed_are_errors() {
+    for base in ["99999", "100000000000", "18446744073709551615"] {
+        let sus = format!("#BPM01: 150\n#00008: 01\n#MEASUREBS {}\n#00110: 11\n", base);
+        let error = parse_sus(&sus).unwrap_err();
+        assert_eq!((error.line, error.column), (4, 2));
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::MeasureOutOfRange { max: 99999, .. }
+        ));
+    }
+}
+
+#[test]
+fn a_chart_without_a_starting_bpm_is_warned_about() {
+    let (chart, warnings) = parse_sus("#BPM01: 150\n#00110: 11\n#00108: 01\n").unwrap();
+    assert_eq!(chart.tempo.changes()[0].bpm, Bpm(12000));
+    let warnings: Vec<String> = warnings.iter().map(|warning| warning.to_string()).collect();
+    assert_eq!(
+        warnings,
+        ["line 3: no BPM at the start of measure 0, so the chart starts at 120"]
+    );
+}
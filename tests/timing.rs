@@ -0,0 +1,99 @@
+use drs_converter::{
+    parse_ssf, Bpm, Chart, Measure, Rational, TempoMap, Timeline, TICKS_PER_MEASURE,
+};
+
+/// Rounds `numer / denom` to the nearest integer, halves up, in plain
+/// integer arithmetic.
+fn round_div(numer: i128, denom: i128) -> i128 {
+    (2 * numer + denom).div_euclid(2 * denom)
+}
+
+fn chart_with(measures: usize, tempo: TempoMap) -> Chart {
+    Chart {
+        tempo,
+        measures: vec![Measure::new(); measures],
+        ..Chart::default()
+    }
+}
+
+#[test]
+fn three_hours_at_173_bpm_never_drift() {
+    // 3 hours of 4/4 at 173 BPM.
+    let measures = 3 * 60 * 173 / 4;
+    let chart = chart_with(measures, TempoMap::new(Bpm(17300)));
+    let timeline = Timeline::new(&chart);
+
+    for measure in 0..measures {
+        for tick in [0, 1, 47, 96, 191] {
+            let position = (measure * TICKS_PER_MEASURE + tick) as i128;
+            // 4 beats of 60000 ms over 192 ticks at 173 BPM.
+            let expected_ms = round_div(position * 4 * 60000, 192 * 173);
+            assert_eq!(
+                timeline.ms_at(measure, tick) as i128,
+                expected_ms,
+                "measure {} tick {}",
+                measure,
+                tick
+            );
+            assert_eq!(timeline.dt_at(measure, tick) as i128, position * 480 * 4 / 192);
+        }
+    }
+}
+
+#[test]
+fn alternating_tempo_changes_never_drift() {
+    let measures = 4000;
+    let mut tempo = TempoMap::new(Bpm(17300));
+    for measure in (1..measures).step_by(2) {
+        tempo.insert(measure, Rational::ZERO, Bpm(12000));
+        tempo.insert(measure + 1, Rational::ZERO, Bpm(17300));
+    }
+    let chart = chart_with(measures, tempo);
+    let timeline = Timeline::new(&chart);
+
+    for pair in 0..measures / 2 {
+        let measure = pair * 2;
+        // Each pair is one measure at 173 BPM plus one at 120 BPM.
+        let pair_ms_numer = 4 * 6000000 * 12000 + 4 * 6000000 * 17300;
+        let expected_ms = round_div(pair as i128 * pair_ms_numer, 17300 * 12000);
+        assert_eq!(timeline.ms_at(measure, 0) as i128, expected_ms, "measure {}", measure);
+        assert_eq!(timeline.dt_at(measure, 0) as usize, measure * 1920);
+    }
+}
+
+#[test]
+fn start_and_end_of_a_hold_round_the_same_way() {
+    let chart = chart_with(1000, TempoMap::new(Bpm(17300)));
+    let timeline = Timeline::new(&chart);
+
+    let start = timeline.exact_ms_at(500, 48);
+    let end = timeline.exact_ms_at(500 + 17, 48);
+    let length = end - start;
+    assert_eq!(length.round(), 17 * 4 * 60000 * 100 / 17300 + 1);
+    assert_eq!(timeline.ms_at(500, 48), start.round() as i64);
+    assert_eq!(timeline.ms_at(517, 48), end.round() as i64);
+}
+
+#[test]
+fn finer_grids_land_between_192_ticks() {
+    let chart = parse_ssf(
+        "#BPM01: 120\n#BPM02: 150\n#00108: 0000000002\n#START\n\
+         0\n0:003\n1 384\n1:003\n2\n383:003\nEND\n",
+    )
+    .unwrap();
+    assert_eq!(chart.measures[1].resolution, 384);
+    assert_eq!(chart.measures[2].resolution, 384);
+
+    let timeline = Timeline::new(&chart);
+    // One 384th of a 2000 ms measure, which no 192-tick position can hold.
+    assert_eq!(timeline.exact_ms_at(1, 1), Rational::new(2000 * 384 + 2000, 384));
+    // The tempo change sits 4/5 through measure 1, at 3600 ms.
+    let tempo_change = chart.tempo.changes()[1];
+    assert_eq!(tempo_change.position, Rational::new(4, 5));
+    assert_eq!(timeline.ms_at_position(1, tempo_change.position), 3600);
+    // The rest of measure 1 takes 320 ms at 150 BPM, then measure 2 is 1600 ms.
+    assert_eq!(
+        timeline.exact_ms_at(2, 383),
+        Rational::integer(3920) + Rational::new(1600 * 383, 384)
+    );
+}
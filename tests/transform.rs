@@ -0,0 +1,180 @@
+mod common;
+
+use common::chart;
+use drs_converter::{parse_ssf, Bpm, Chart, Hold, NoteEvent, Rational, TransformError};
+
+const HOLDS: &str = "0
+0:003,5183
+48:4023,2
+96:6143,8093
+144:7163,A0A3
+1
+0:133,3
+";
+
+#[test]
+fn mirror_swaps_feet_and_skid_direction() {
+    let mut mirrored = chart("0\n0:003,4143\n96:B14323\n192:1C3\n");
+    mirrored.mirror().unwrap();
+    // The skid moved left, from lane 4 to lane 2, and now moves right.
+    assert_eq!(mirrored, chart("0\n0:1C3,5183\n96:B183A3\n192:003\n"));
+
+    let mut twice = mirrored.clone();
+    twice.mirror().unwrap();
+    assert_eq!(twice, chart("0\n0:003,4143\n96:B14323\n192:1C3\n"));
+}
+
+#[test]
+fn shift_keeps_notes_on_the_pad() {
+    let mut shifted = chart("0\n0:003,1C3\n");
+    assert_eq!(
+        shifted.clone().shift_lanes(1),
+        Err(TransformError::LaneOutOfRange {
+            measure: 0,
+            tick: 0,
+            note: NoteEvent::RightStep { lane: 12, width: 4 }
+        })
+    );
+    assert_eq!(
+        shifted.clone().shift_lanes(-1),
+        Err(TransformError::LaneOutOfRange {
+            measure: 0,
+            tick: 0,
+            note: NoteEvent::LeftStep { lane: 0, width: 4 }
+        })
+    );
+    shifted.shift_lanes(0).unwrap();
+    assert_eq!(shifted, chart("0\n0:003,1C3\n"));
+
+    let mut shifted = chart("0\n0:043,183\n");
+    shifted.shift_lanes(-4).unwrap();
+    assert_eq!(shifted, chart("0\n0:003,143\n"));
+}
+
+#[test]
+fn random_lanes_depend_only_on_the_seed() {
+    let original = chart(HOLDS);
+    let randomized = |seed| {
+        let mut chart = original.clone();
+        chart.randomize_lanes(seed).unwrap();
+        chart
+    };
+    assert_eq!(randomized(7), randomized(7));
+    assert_ne!(randomized(7), randomized(8));
+}
+
+/// The lane and width of each span of a hold note.
+fn spans(event: &NoteEvent) -> Vec<(u8, u8)> {
+    match *event {
+        NoteEvent::LeftHoldStart { lane, width, .. }
+        | NoteEvent::RightHoldStart { lane, width, .. }
+        | NoteEvent::SlideWaypoint { lane, width, .. }
+        | NoteEvent::SlideEnd { lane, width, .. }
+        | NoteEvent::SimpleSkidWaypoint { lane, width, .. }
+        | NoteEvent::SimpleSkidEnd { lane, width, .. } => vec![(lane, width)],
+        NoteEvent::ComplexSkidWaypoint {
+            lane_start,
+            width_start,
+            lane_end,
+            width_end,
+            ..
+        }
+        | NoteEvent::ComplexSkidEnd {
+            lane_start,
+            width_start,
+            lane_end,
+            width_end,
+            ..
+        } => vec![(lane_start, width_start), (lane_end, width_end)],
+        _ => vec![],
+    }
+}
+
+/// Each span of a hold's points, relative to where the hold starts.
+fn shape(hold: &Hold) -> Vec<(i32, u8)> {
+    let start = spans(&hold.start)[0].0 as i32;
+    hold.points
+        .iter()
+        .flat_map(|point| spans(&point.event))
+        .map(|(lane, width)| (lane as i32 - start, width))
+        .collect()
+}
+
+#[test]
+fn random_lanes_keep_hold_shapes() {
+    let original = chart(HOLDS);
+    let holds = original.holds().unwrap();
+    for seed in 0..32 {
+        let mut randomized = original.clone();
+        randomized.randomize_lanes(seed).unwrap();
+        let moved = randomized.holds().unwrap();
+        assert_eq!(moved.len(), holds.len());
+        for (before, after) in holds.iter().zip(&moved) {
+            assert_eq!(shape(after), shape(before), "seed {}", seed);
+            assert_eq!(after.points.len(), before.points.len());
+        }
+    }
+}
+
+fn bpms(chart: &Chart) -> Vec<u32> {
+    chart
+        .tempo
+        .changes()
+        .iter()
+        .map(|change| change.bpm.0)
+        .collect()
+}
+
+#[test]
+fn rate_scales_tempos_and_offset() {
+    let mut scaled =
+        parse_ssf("#BPM01: 150\n#BPM02: 99.5\n#00108: 0002\n#START\n0\n0:003\nEND\n").unwrap();
+    scaled.header.wave_offset = Some(1000);
+    let original = scaled.clone();
+    scaled.scale_rate("6/5".parse().unwrap()).unwrap();
+    assert_eq!(bpms(&scaled), vec![18000, 11940]);
+    // WAVEOFFSET is a time in the audio, so it shrinks as the audio speeds
+    // up, rounded to the millisecond.
+    assert_eq!(scaled.header.wave_offset, Some(833));
+    assert_eq!(scaled.events, original.events);
+
+    let mut slowed = original.clone();
+    slowed.scale_rate("0.8".parse().unwrap()).unwrap();
+    assert_eq!(bpms(&slowed), vec![12000, 7960]);
+    assert_eq!(slowed.header.wave_offset, Some(1250));
+}
+
+#[test]
+fn rate_errors() {
+    let mut original = chart("0\n0:003\n");
+    for rate in ["0", "-1"] {
+        let rate: Rational = rate.parse().unwrap();
+        assert_eq!(
+            original.clone().scale_rate(rate),
+            Err(TransformError::InvalidRate(rate))
+        );
+    }
+
+    // 120 BPM at 1/7 would be 17.142857... BPM.
+    let rate = Rational::new(1, 7);
+    assert_eq!(
+        original.clone().scale_rate(rate),
+        Err(TransformError::InexactRate {
+            rate,
+            bpm: Bpm(12000)
+        })
+    );
+    assert_eq!(
+        original.clone().scale_rate(Rational::integer(1 << 30)),
+        Err(TransformError::BpmOutOfRange {
+            measure: 0,
+            position: Rational::ZERO
+        })
+    );
+
+    original.header.wave_offset = Some(i32::MAX);
+    assert_eq!(
+        original.scale_rate("1/2".parse().unwrap()),
+        Err(TransformError::OffsetOutOfRange)
+    );
+}
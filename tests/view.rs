@@ -0,0 +1,37 @@
+use drs_converter::{parse_ssf, GridStyle, GridView};
+
+#[test]
+fn grid_shows_notes_holds_and_measures() {
+    let chart = parse_ssf(
+        "#BPM01: 120
+#START
+0
+0:003,2
+96:41A3
+1
+0:7183
+2
+0:133,3
+END
+",
+    )
+    .unwrap();
+    let view = GridView::new(&chart, GridStyle::Ascii).unwrap();
+    assert_eq!(view.measure_count(), 3);
+    assert_eq!(
+        view.page(0..2),
+        "       m0 +----------------+
+        0 |<<<<^^^^^^^^^^^^|
+      500 |                |
+     1000 |          <<<<  |
+     1500 |         ||||   |
+       m1 +----------------+
+     2000 |        oooo    |
+     2500 |                |
+     3000 |                |
+     3500 |                |
+       m2 +----------------+
+"
+    );
+    assert!(view.page(2..3).contains("     4000 |vvv>>>>vvvvvvvvv|"));
+}